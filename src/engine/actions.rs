@@ -0,0 +1,311 @@
+use std::{collections::BTreeMap, path::Path};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+use ubsg::{game::Inputs, ron_file::{self, RonFileError}};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    SonicDrop, // drops without locking
+    RotateCW,
+    RotateCCW,
+    Rotate180,
+    Hold,
+    Restart,
+    Pause
+}
+
+pub const ACTIONS: [Action; 11] = [
+    Action::MoveLeft, Action::MoveRight, Action::SoftDrop, Action::HardDrop, Action::SonicDrop,
+    Action::RotateCW, Action::RotateCCW, Action::Rotate180, Action::Hold, Action::Restart, Action::Pause
+];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::SoftDrop => "Soft drop",
+            Action::HardDrop => "Hard drop",
+            Action::SonicDrop => "Sonic drop",
+            Action::RotateCW => "Rotate CW",
+            Action::RotateCCW => "Rotate CCW",
+            Action::Rotate180 => "Rotate 180",
+            Action::Hold => "Hold",
+            Action::Restart => "Restart",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+// Gamepad button or stick direction. Stick counts as held when it is pushed past the deadzone
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PadInput {
+    Button(GamepadButtonType),
+    AxisPositive(GamepadAxisType),
+    AxisNegative(GamepadAxisType)
+}
+
+impl PadInput {
+    fn name(&self) -> String {
+        match self {
+            PadInput::Button(button) => format!("Pad {:?}", button),
+            PadInput::AxisPositive(axis) => format!("Pad {:?}+", axis),
+            PadInput::AxisNegative(axis) => format!("Pad {:?}-", axis),
+        }
+    }
+}
+
+pub const STICK_AXES: [GamepadAxisType; 4] = [
+    GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY, GamepadAxisType::RightStickX, GamepadAxisType::RightStickY
+];
+
+// Everything, that can trigger an action
+#[derive(SystemParam)]
+pub struct InputDevices<'w> {
+    pub keyboard: Res<'w, Input<KeyCode>>,
+    pub buttons: Res<'w, Input<GamepadButton>>,
+    pub axes: Res<'w, Axis<GamepadAxis>>,
+    pub gamepads: Res<'w, Gamepads>,
+    pub controls: Res<'w, Controls>
+}
+
+impl InputDevices<'_> {
+    // Connected gamepads in order of their ids, so numbers don't jump around on hot-plug
+    pub fn connected(&self) -> Vec<Gamepad> {
+        let mut gamepads: Vec<Gamepad> = self.gamepads.iter().collect();
+        gamepads.sort_by_key(|gamepad| gamepad.id);
+        gamepads
+    }
+
+    fn held(&self, gamepad: Gamepad, input: PadInput) -> bool {
+        let deadzone = self.controls.stick_deadzone;
+        match input {
+            PadInput::Button(button) => self.buttons.pressed(GamepadButton::new(gamepad, button)),
+            PadInput::AxisPositive(axis) => self.axes.get(GamepadAxis::new(gamepad, axis)).unwrap_or(0.0) > deadzone,
+            PadInput::AxisNegative(axis) => self.axes.get(GamepadAxis::new(gamepad, axis)).unwrap_or(0.0) < -deadzone,
+        }
+    }
+
+    // Sticks have no press moment, so only buttons count
+    fn pressed_now(&self, gamepad: Gamepad, input: PadInput) -> bool {
+        match input {
+            PadInput::Button(button) => self.buttons.just_pressed(GamepadButton::new(gamepad, button)),
+            _ => false,
+        }
+    }
+}
+
+// Any of the keys or pad inputs bound to an action triggers it
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActionMap {
+    pub keys: BTreeMap<Action, Vec<KeyCode>>,
+    #[serde(default = "ActionMap::default_pad")] // files saved before gamepads were supported
+    pub pad: BTreeMap<Action, Vec<PadInput>>,
+    pub gamepad: Option<usize> // n-th connected gamepad, any of them if None
+}
+
+impl ActionMap {
+    pub fn create(keys: &[(Action, &[KeyCode])], gamepad: Option<usize>) -> ActionMap {
+        ActionMap {
+            keys: keys.iter().map(|(action, keys)| (*action, keys.to_vec())).collect(),
+            pad: ActionMap::default_pad(),
+            gamepad
+        }
+    }
+
+    // Same layout for everyone: D-pad or left stick to move, face buttons to rotate
+    fn default_pad() -> BTreeMap<Action, Vec<PadInput>> {
+        let pad: [(Action, &[PadInput]); 11] = [
+            (Action::MoveLeft, &[PadInput::Button(GamepadButtonType::DPadLeft), PadInput::AxisNegative(GamepadAxisType::LeftStickX)]),
+            (Action::MoveRight, &[PadInput::Button(GamepadButtonType::DPadRight), PadInput::AxisPositive(GamepadAxisType::LeftStickX)]),
+            (Action::SoftDrop, &[PadInput::Button(GamepadButtonType::DPadDown), PadInput::AxisNegative(GamepadAxisType::LeftStickY)]),
+            (Action::HardDrop, &[PadInput::Button(GamepadButtonType::DPadUp)]),
+            (Action::SonicDrop, &[]),
+            (Action::RotateCW, &[PadInput::Button(GamepadButtonType::East)]),
+            (Action::RotateCCW, &[PadInput::Button(GamepadButtonType::South)]),
+            (Action::Rotate180, &[PadInput::Button(GamepadButtonType::North)]),
+            (Action::Hold, &[PadInput::Button(GamepadButtonType::LeftTrigger), PadInput::Button(GamepadButtonType::RightTrigger)]),
+            (Action::Restart, &[PadInput::Button(GamepadButtonType::Select)]),
+            (Action::Pause, &[PadInput::Button(GamepadButtonType::Start)]),
+        ];
+        pad.iter().map(|(action, inputs)| (*action, inputs.to_vec())).collect()
+    }
+
+    pub fn solo() -> ActionMap {
+        ActionMap::create(&[
+            (Action::MoveLeft, &[KeyCode::Left]),
+            (Action::MoveRight, &[KeyCode::Right]),
+            (Action::SoftDrop, &[KeyCode::Down]),
+            (Action::HardDrop, &[KeyCode::Space]),
+            (Action::SonicDrop, &[]),
+            (Action::RotateCW, &[KeyCode::Up, KeyCode::X]),
+            (Action::RotateCCW, &[KeyCode::Z]),
+            (Action::Rotate180, &[KeyCode::A]),
+            (Action::Hold, &[KeyCode::C]),
+            (Action::Restart, &[KeyCode::R]),
+            (Action::Pause, &[KeyCode::Escape]),
+        ], None)
+    }
+
+    // Versus: left side of keyboard for player one, right side for player two
+    pub fn player_one() -> ActionMap {
+        ActionMap::create(&[
+            (Action::MoveLeft, &[KeyCode::A]),
+            (Action::MoveRight, &[KeyCode::D]),
+            (Action::SoftDrop, &[KeyCode::S]),
+            (Action::HardDrop, &[KeyCode::W]),
+            (Action::SonicDrop, &[]),
+            (Action::RotateCW, &[KeyCode::E]),
+            (Action::RotateCCW, &[KeyCode::Q]),
+            (Action::Rotate180, &[KeyCode::F]),
+            (Action::Hold, &[KeyCode::ShiftLeft]),
+            (Action::Restart, &[KeyCode::R]),
+            (Action::Pause, &[KeyCode::Escape]),
+        ], Some(0))
+    }
+
+    pub fn player_two() -> ActionMap {
+        ActionMap::create(&[
+            (Action::MoveLeft, &[KeyCode::Left]),
+            (Action::MoveRight, &[KeyCode::Right]),
+            (Action::SoftDrop, &[KeyCode::Down]),
+            (Action::HardDrop, &[KeyCode::Up]),
+            (Action::SonicDrop, &[]),
+            (Action::RotateCW, &[KeyCode::Slash]),
+            (Action::RotateCCW, &[KeyCode::Period]),
+            (Action::Rotate180, &[KeyCode::Semicolon]),
+            (Action::Hold, &[KeyCode::ShiftRight]),
+            (Action::Restart, &[]),
+            (Action::Pause, &[]),
+        ], Some(1))
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        match self.keys.get(&action) {
+            Some(keys) => keys,
+            None => &[],
+        }
+    }
+
+    pub fn pad_inputs(&self, action: Action) -> &[PadInput] {
+        match self.pad.get(&action) {
+            Some(inputs) => inputs,
+            None => &[],
+        }
+    }
+
+    pub fn bind(&mut self, action: Action, key: KeyCode) {
+        let keys = self.keys.entry(action).or_default();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    pub fn bind_pad(&mut self, action: Action, input: PadInput) {
+        let inputs = self.pad.entry(action).or_default();
+        if !inputs.contains(&input) {
+            inputs.push(input);
+        }
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.keys.insert(action, vec![]);
+        self.pad.insert(action, vec![]);
+    }
+
+    // Gamepads, that this map listens to. Resolved every time, so pads can be plugged in mid-game
+    fn gamepads(&self, devices: &InputDevices) -> Vec<Gamepad> {
+        let connected = devices.connected();
+        match self.gamepad {
+            Some(index) => connected.get(index).copied().into_iter().collect(),
+            None => connected,
+        }
+    }
+
+    pub fn pressed(&self, devices: &InputDevices, action: Action) -> bool {
+        devices.keyboard.any_pressed(self.keys(action).iter().copied()) ||
+            self.gamepads(devices).into_iter().any(|gamepad| self.pad_inputs(action).iter().any(|input| devices.held(gamepad, *input)))
+    }
+
+    pub fn just_pressed(&self, devices: &InputDevices, action: Action) -> bool {
+        devices.keyboard.any_just_pressed(self.keys(action).iter().copied()) ||
+            self.gamepads(devices).into_iter().any(|gamepad| self.pad_inputs(action).iter().any(|input| devices.pressed_now(gamepad, *input)))
+    }
+
+    pub fn inputs(&self, devices: &InputDevices) -> Inputs {
+        Inputs {
+            left: self.pressed(devices, Action::MoveLeft),
+            right: self.pressed(devices, Action::MoveRight),
+            soft_drop: self.pressed(devices, Action::SoftDrop),
+            hard_drop: self.pressed(devices, Action::HardDrop),
+            rotate_cw: self.pressed(devices, Action::RotateCW),
+            rotate_ccw: self.pressed(devices, Action::RotateCCW),
+            hold: self.pressed(devices, Action::Hold),
+            sonic_drop: self.pressed(devices, Action::SonicDrop),
+            rotate_180: self.pressed(devices, Action::Rotate180),
+        }
+    }
+
+    // "Left, X, Pad South" or "-" when nothing is bound
+    pub fn describe(&self, action: Action) -> String {
+        let mut names: Vec<String> = self.keys(action).iter().map(|key| format!("{:?}", key)).collect();
+        names.extend(self.pad_inputs(action).iter().map(|input| input.name()));
+        if names.is_empty() {
+            String::from("-")
+        } else {
+            names.join(", ")
+        }
+    }
+}
+
+// Key bindings for every seat, saved to CONTROLS_PATH
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controls {
+    pub solo: ActionMap,
+    pub player_one: ActionMap,
+    pub player_two: ActionMap,
+    pub stick_deadzone: f32 // 0.0 - 1.0
+}
+
+pub const CONTROL_SETS: [&str; 3] = ["Solo", "Player 1", "Player 2"];
+pub const DEFAULT_STICK_DEADZONE: f32 = 0.5;
+
+impl Default for Controls {
+    fn default() -> Controls {
+        Controls { solo: ActionMap::solo(), player_one: ActionMap::player_one(), player_two: ActionMap::player_two(), stick_deadzone: DEFAULT_STICK_DEADZONE }
+    }
+}
+
+impl Controls {
+    // Index in CONTROL_SETS
+    pub fn set_mut(&mut self, index: usize) -> &mut ActionMap {
+        match index {
+            1 => &mut self.player_one,
+            2 => &mut self.player_two,
+            _ => &mut self.solo,
+        }
+    }
+
+    pub fn set(&self, index: usize) -> &ActionMap {
+        match index {
+            1 => &self.player_one,
+            2 => &self.player_two,
+            _ => &self.solo,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), RonFileError> {
+        ron_file::save(self, path)
+    }
+
+    pub fn load(path: &Path) -> Result<Controls, RonFileError> {
+        ron_file::load(path)
+    }
+}
@@ -1,18 +1,61 @@
 use bevy::prelude::*;
+use ubsg::{game::{Engine, Inputs}, replay::{Replay, ReplayPlayer}};
+use super::actions::ActionMap;
+
+// Board with its own engine. Minos and texts of the board are its children
+#[derive(Component, Deref, DerefMut)]
+pub struct Playfield(pub Engine);
+
+// Inputs collected by Update systems until the next fixed frame
+#[derive(Component, Default)]
+pub struct PendingInputs(pub Inputs);
+
+// 0 for the first player
+#[derive(Component, Clone, Copy)]
+pub struct Player(pub usize);
+
+// Controls of the player, who plays on that playfield
+#[derive(Component)]
+pub struct Bindings(pub ActionMap);
+
+// Replay of the current game, saved when game ends or restarts
+#[derive(Component)]
+pub struct ReplayRecorder(pub Replay);
+
+// Feeds inputs from replay instead of keyboard
+#[derive(Component)]
+pub struct PlaybackPlayer(pub ReplayPlayer);
 
 #[derive(Component)]
 pub struct BoardVisual{}
 
 #[derive(Component)]
-pub struct HUD {}
+pub struct Hud {}
 
 #[derive(Component)]
 pub struct LockDelayText {}
 
+#[derive(Component)]
+pub struct ScoreText {}
+
+#[derive(Component)]
+pub struct PlacementText {}
+
+
+#[derive(Component)]
+pub struct PauseText {}
+
+#[derive(Component)]
+pub struct MenuText {}
+
+#[derive(Component)]
+pub struct ControlsText {}
+
+#[derive(Component)]
+pub struct ResultsText {}
+
 #[derive(Component)]
 pub struct UImino {}
 
 #[derive(Component, Clone, Copy)]
-pub struct Mino{
-    pub color: Color
-}
\ No newline at end of file
+pub struct Mino {}
\ No newline at end of file
@@ -0,0 +1,321 @@
+use super::{components::{ControlsText, MenuText}, resources::{ControlsCursor, GameSettings, MenuCursor, Playback}, actions::{Controls, PadInput, ACTIONS, CONTROL_SETS, STICK_AXES}, GameStates};
+use std::path::Path;
+
+use bevy::prelude::*;
+use ubsg::{attack::ATTACK_TABLES, scoring::SCORING_TABLES, settings::UserSettings, modes::GAME_MODES, randomizers::RANDOMIZERS, rotation_systems::ROTATION_SYSTEMS};
+
+const MENU_ROWS: usize = 13;
+const CONTROLS_ROW: usize = 11;
+const SETTINGS_PATH: &str = "settings.ron";
+const CONTROLS_PATH: &str = "controls.ron";
+const MAX_NEXT: u8 = 6;
+const DEADZONE_STEP: f32 = 0.05;
+const GAMEPAD_CHOICES: [Option<usize>; 5] = [None, Some(0), Some(1), Some(2), Some(3)];
+
+fn rotation_system_names() -> Vec<String> {
+    let mut names: Vec<String> = ROTATION_SYSTEMS.keys().cloned().collect();
+    names.sort();
+    names
+}
+
+// Picks the neighbour of current value, wrapping around
+fn cycle<T: PartialEq + Clone>(values: &[T], current: &T, direction: isize) -> T {
+    let index = values.iter().position(|value| value == current).unwrap_or(0) as isize;
+    let len = values.len() as isize;
+    values[((index + direction) % len + len) as usize % values.len()].clone()
+}
+
+fn menu_text(settings: &GameSettings, cursor: usize) -> String {
+    let (rotation_system, randomizer) = settings.mode.required_rules().unwrap_or((&settings.user.rotation_system, &settings.user.randomizer));
+    let rows = [
+        format!("Mode: {}", settings.mode.name()),
+        format!("Rotation system: {}", rotation_system),
+        format!("Randomizer: {}", randomizer),
+        format!("Next pieces: {}", settings.user.next_count),
+        format!("Hold: {}", if settings.hold { "on" } else { "off" }),
+        format!("Ghost: {}", if settings.user.ghost { "on" } else { "off" }),
+        format!("Grid: {}", if settings.user.grid { "on" } else { "off" }),
+        format!("Attack table: {}", settings.attack_table.name()),
+        format!("Scoring: {}", settings.mode.scoring_table(settings.scoring).name()),
+        format!("Players: {}", if settings.versus { 2 } else { 1 }),
+        format!("Same seed: {}", if settings.same_seed { "on" } else { "off" }),
+        String::from("Controls"),
+        String::from("Start"),
+    ];
+    let mut text = String::new();
+    for (i, row) in rows.iter().enumerate() {
+        text += if i == cursor { "> " } else { "  " };
+        text += row;
+        text += "\n";
+    }
+    text
+}
+
+// Missing file is fine, defaults are used then
+pub fn load_settings(mut settings: ResMut<GameSettings>) {
+    match UserSettings::load(Path::new(SETTINGS_PATH)) {
+        Ok(mut user) => {
+            for problem in user.reset_unknown_names() {
+                error!("Settings: {}", problem);
+            }
+            settings.user = user;
+        },
+        Err(error) if error.is_not_found() => {},
+        Err(error) => error!("Settings were not loaded: {}", error),
+    }
+}
+
+pub fn save_settings(settings: Res<GameSettings>) {
+    if let Err(error) = settings.user.save(Path::new(SETTINGS_PATH)) {
+        error!("Settings were not saved: {}", error);
+    }
+}
+
+pub fn load_controls(mut controls: ResMut<Controls>) {
+    match Controls::load(Path::new(CONTROLS_PATH)) {
+        Ok(loaded) => *controls = loaded,
+        Err(error) if error.is_not_found() => {},
+        Err(error) => error!("Controls were not loaded: {}", error),
+    }
+}
+
+pub fn save_controls(controls: Res<Controls>) {
+    if let Err(error) = controls.save(Path::new(CONTROLS_PATH)) {
+        error!("Controls were not saved: {}", error);
+    }
+}
+
+// Replays already know their rules
+pub fn skip_menu_on_playback(playback: Option<Res<Playback>>, mut game_next_state: ResMut<NextState<GameStates>>) {
+    if playback.is_some() {
+        game_next_state.set(GameStates::Init);
+    }
+}
+
+pub fn spawn_menu(mut commands: Commands, asset_server: Res<AssetServer>, settings: Res<GameSettings>) {
+    commands.insert_resource(MenuCursor(0));
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(30.0),
+                left: Val::Percent(30.0),
+                ..default()
+            },
+            text: Text {
+                sections: vec![TextSection::new(
+                    menu_text(&settings, 0),
+                    TextStyle {
+                        font: asset_server.load("EurostileRound-Regular.ttf"),
+                        font_size: 32.0,
+                        color: Color::rgb(1.0, 1.0, 1.0),
+                    },
+                )],
+                alignment: TextAlignment::Left,
+                ..default()
+            },
+            ..default()
+        },
+        MenuText {},
+    ));
+}
+
+pub fn despawn_menu(mut commands: Commands, menu_query: Query<Entity, With<MenuText>>) {
+    for entity in menu_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn receive_input_on_menu(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut cursor: ResMut<MenuCursor>,
+    mut settings: ResMut<GameSettings>,
+    mut menu_text_query: Query<&mut Text, With<MenuText>>,
+    mut game_next_state: ResMut<NextState<GameStates>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        cursor.0 = (cursor.0 + MENU_ROWS - 1) % MENU_ROWS;
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        cursor.0 = (cursor.0 + 1) % MENU_ROWS;
+    }
+    let direction = if keyboard_input.just_pressed(KeyCode::Left) {
+        -1
+    } else if keyboard_input.just_pressed(KeyCode::Right) {
+        1
+    } else {
+        0
+    };
+    if direction != 0 {
+        match cursor.0 {
+            0 => settings.mode = cycle(&GAME_MODES, &settings.mode, direction),
+            1 => settings.user.rotation_system = cycle(&rotation_system_names(), &settings.user.rotation_system, direction),
+            2 => {
+                let randomizers: Vec<String> = RANDOMIZERS.iter().map(|name| String::from(*name)).collect();
+                settings.user.randomizer = cycle(&randomizers, &settings.user.randomizer, direction);
+            },
+            3 => settings.user.next_count = (settings.user.next_count as isize + direction).clamp(0, MAX_NEXT as isize) as u8,
+            4 => settings.hold = !settings.hold,
+            5 => settings.user.ghost = !settings.user.ghost,
+            6 => settings.user.grid = !settings.user.grid,
+            7 => settings.attack_table = cycle(&ATTACK_TABLES, &settings.attack_table, direction),
+            8 => settings.scoring = cycle(&SCORING_TABLES, &settings.scoring, direction),
+            9 => settings.versus = !settings.versus,
+            10 => settings.same_seed = !settings.same_seed,
+            _ => {},
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        match cursor.0 {
+            CONTROLS_ROW => game_next_state.set(GameStates::Controls),
+            row if row == MENU_ROWS - 1 => game_next_state.set(GameStates::Init),
+            _ => {},
+        }
+    }
+    if cursor.is_changed() || settings.is_changed() {
+        for mut text in menu_text_query.iter_mut() {
+            text.sections[0].value = menu_text(&settings, cursor.0);
+        }
+    }
+}
+
+fn controls_text(controls: &Controls, cursor: &ControlsCursor) -> String {
+    let bindings = controls.set(cursor.set);
+    let mut text = format!("Controls: < {} >\n\n", CONTROL_SETS[cursor.set]);
+    let gamepad = match bindings.gamepad {
+        Some(index) => format!("{}", index + 1),
+        None => String::from("any"),
+    };
+    let mut rows: Vec<String> = ACTIONS.iter().map(|action| format!("{}: {}", action.name(), bindings.describe(*action))).collect();
+    rows.push(format!("Gamepad: {}", gamepad));
+    rows.push(format!("Stick deadzone: {:.2}", controls.stick_deadzone));
+    for (i, row) in rows.iter().enumerate() {
+        text += if i == cursor.row { "> " } else { "  " };
+        text += row;
+        if i == cursor.row && cursor.waiting {
+            text += " + ?";
+        }
+        text += "\n";
+    }
+    text += if cursor.waiting {
+        "\nPress a key or a gamepad button to bind it"
+    } else if cursor.row >= ACTIONS.len() {
+        "\nLeft/Right - change, Escape - back"
+    } else {
+        "\nEnter - add key, Backspace - clear, Left/Right - switch player, Escape - back"
+    };
+    text
+}
+
+pub fn spawn_controls(mut commands: Commands, asset_server: Res<AssetServer>, controls: Res<Controls>) {
+    let cursor = ControlsCursor::default();
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(20.0),
+                left: Val::Percent(25.0),
+                ..default()
+            },
+            text: Text {
+                sections: vec![TextSection::new(
+                    controls_text(&controls, &cursor),
+                    TextStyle {
+                        font: asset_server.load("EurostileRound-Regular.ttf"),
+                        font_size: 32.0,
+                        color: Color::rgb(1.0, 1.0, 1.0),
+                    },
+                )],
+                alignment: TextAlignment::Left,
+                ..default()
+            },
+            ..default()
+        },
+        ControlsText {},
+    ));
+    commands.insert_resource(cursor);
+}
+
+pub fn despawn_controls(mut commands: Commands, controls_query: Query<Entity, With<ControlsText>>) {
+    for entity in controls_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Stick pushed past the deadzone on any connected gamepad
+fn pushed_stick(axes: &Axis<GamepadAxis>, gamepads: &Gamepads, deadzone: f32) -> Option<PadInput> {
+    for gamepad in gamepads.iter() {
+        for axis in STICK_AXES {
+            let value = axes.get(GamepadAxis::new(gamepad, axis)).unwrap_or(0.0);
+            if value > deadzone {
+                return Some(PadInput::AxisPositive(axis));
+            } else if value < -deadzone {
+                return Some(PadInput::AxisNegative(axis));
+            }
+        }
+    }
+    None
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn receive_input_on_controls(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    mut cursor: ResMut<ControlsCursor>,
+    mut controls: ResMut<Controls>,
+    mut controls_text_query: Query<&mut Text, With<ControlsText>>,
+    mut game_next_state: ResMut<NextState<GameStates>>,
+) {
+    let rows = ACTIONS.len() + 2; // actions, gamepad, deadzone
+    let direction = if keyboard_input.just_pressed(KeyCode::Left) {
+        -1
+    } else if keyboard_input.just_pressed(KeyCode::Right) {
+        1
+    } else {
+        0
+    };
+    if cursor.waiting {
+        let action = ACTIONS[cursor.row];
+        let deadzone = controls.stick_deadzone;
+        let set = controls.set_mut(cursor.set);
+        // any key can be bound, even the ones used to navigate this screen
+        if let Some(key) = keyboard_input.get_just_pressed().next() {
+            set.bind(action, *key);
+            cursor.waiting = false;
+        } else if let Some(button) = gamepad_buttons.get_just_pressed().next() {
+            set.bind_pad(action, PadInput::Button(button.button_type));
+            cursor.waiting = false;
+        } else if let Some(input) = pushed_stick(&gamepad_axes, &gamepads, deadzone) {
+            set.bind_pad(action, input);
+            cursor.waiting = false;
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        game_next_state.set(GameStates::Menu);
+    } else if keyboard_input.just_pressed(KeyCode::Up) {
+        cursor.row = (cursor.row + rows - 1) % rows;
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        cursor.row = (cursor.row + 1) % rows;
+    } else if cursor.row == ACTIONS.len() {
+        if direction != 0 {
+            let set = controls.set_mut(cursor.set);
+            set.gamepad = cycle(&GAMEPAD_CHOICES, &set.gamepad, direction);
+        }
+    } else if cursor.row == ACTIONS.len() + 1 {
+        if direction != 0 {
+            controls.stick_deadzone = (controls.stick_deadzone + direction as f32 * DEADZONE_STEP).clamp(DEADZONE_STEP, 1.0 - DEADZONE_STEP);
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Return) {
+        cursor.waiting = true;
+    } else if keyboard_input.just_pressed(KeyCode::Back) {
+        controls.set_mut(cursor.set).clear(ACTIONS[cursor.row]);
+    } else if direction != 0 {
+        cursor.set = (cursor.set as isize + direction + CONTROL_SETS.len() as isize) as usize % CONTROL_SETS.len();
+    }
+    if cursor.is_changed() || controls.is_changed() {
+        for mut text in controls_text_query.iter_mut() {
+            text.sections[0].value = controls_text(&controls, &cursor);
+        }
+    }
+}
@@ -1,50 +1,58 @@
 use bevy::prelude::*;
-use self::{systems::*, resources::Engine, ui::spawn_hud};
+use self::{systems::*, menu::*, resources::GameSettings, actions::Controls, ui::{spawn_hud, despawn_hud}};
 
-mod rotation_systems;
 mod systems;
+mod actions;
+mod menu;
 mod components;
 mod resources;
 mod ui;
-pub mod randomizers;
 
+pub use resources::Playback;
+
+// Bevy side of the game: collects inputs through the action map, steps the engine every fixed frame and draws it
 pub struct UBSGEngine;
 
 impl Plugin for UBSGEngine{
     fn build(&self, app: &mut App) {
-        app.init_resource::<Engine>().
+        app.init_resource::<GameSettings>().
+            init_resource::<Controls>().
             add_state::<GameStates>().
-            add_state::<GameloopStates>().
-            insert_resource(Engine::default()).
-            //add_systems(Startup, init_engine).
-            add_systems(Startup, spawn_hud).
-            add_systems(OnEnter(GameloopStates::Init), init_engine).
+            add_systems(Startup, (load_settings, load_controls, skip_menu_on_playback)).
+            add_systems(OnEnter(GameStates::Menu), (despawn_board, despawn_hud, spawn_menu)).
+            add_systems(OnExit(GameStates::Menu), (despawn_menu, save_settings)).
+            add_systems(OnTransition { from: GameStates::Menu, to: GameStates::Init }, spawn_hud).
+            add_systems(Update, receive_input_on_menu.run_if(in_state(GameStates::Menu))).
+            add_systems(OnEnter(GameStates::Controls), spawn_controls).
+            add_systems(OnExit(GameStates::Controls), (despawn_controls, save_controls)).
+            add_systems(Update, receive_input_on_controls.run_if(in_state(GameStates::Controls))).
+            add_systems(Update, log_gamepad_connections).
+            add_systems(OnEnter(GameStates::Init), init_engine).
             add_systems(Update, receive_input.run_if(in_state(GameStates::Gameplay))).
-            add_systems(Update, das_and_arr.run_if(in_state(GameStates::Gameplay))).
-            add_systems(FixedUpdate, gameloop.run_if(in_state(GameStates::Gameplay)).run_if(in_state(GameloopStates::Falling))).
-            add_systems(FixedUpdate, run_spawn_delay.run_if(in_state(GameStates::Gameplay)).run_if(in_state(GameloopStates::Spawn))).
-            add_systems(OnEnter(GameloopStates::AfterLocking), after_locking_routine).
-            add_systems(OnEnter(GameloopStates::Falling), draw_next).
-            add_systems(OnExit(GameloopStates::Spawn), spawn_routine).
-            add_systems(Update, draw_board.run_if(in_state(GameStates::Gameplay))).
-            add_systems(Update, receive_input_on_game_over.run_if(in_state(GameStates::GameOver)));
+            add_systems(Update, pause_game.run_if(in_state(GameStates::Gameplay).or_else(in_state(GameStates::Countdown)))).
+            add_systems(Update, receive_input_on_pause.run_if(in_state(GameStates::Pause))).
+            add_systems(OnEnter(GameStates::Pause), hide_board).
+            add_systems(OnExit(GameStates::Pause), clear_pause_text).
+            add_systems(OnEnter(GameStates::Countdown), start_resume_countdown).
+            add_systems(FixedUpdate, run_resume_countdown.run_if(in_state(GameStates::Countdown))).
+            add_systems(FixedUpdate, gameloop.run_if(in_state(GameStates::Gameplay))).
+            add_systems(Update, draw_board.run_if(in_state(GameStates::Gameplay).or_else(in_state(GameStates::Countdown)))).
+            add_systems(Update, draw_next.run_if(in_state(GameStates::Gameplay).or_else(in_state(GameStates::Countdown)))).
+            add_systems(OnEnter(GameStates::Results), spawn_results).
+            add_systems(OnExit(GameStates::Results), despawn_results).
+            add_systems(Update, receive_input_on_game_over.run_if(in_state(GameStates::GameOver).or_else(in_state(GameStates::Results))));
     }
 }
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum GameStates{
     #[default]
+    Menu,
+    Controls, // key bindings screen
     Init,
     Gameplay,
     Pause,
-    GameOver
+    Countdown, // 3-2-1 before resuming from pause
+    GameOver,
+    Results // mode was finished
 }
-
-#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
-pub enum GameloopStates {
-    #[default]
-    Init,
-    Spawn,
-    Falling,
-    AfterLocking
-}
\ No newline at end of file
@@ -1,140 +1,279 @@
-use std::{thread, time::Duration};
+use std::{mem::take, path::PathBuf, time::SystemTime};
 
-use super::{resources::Engine, rotation_systems::LockDelayMode, GameStates, GameloopStates, randomizers::*};
+use super::{resources::{Playback, ResumeCountdown, GameSettings}, actions::{Action, ActionMap, Controls, InputDevices}, GameStates};
 use crate::engine::components::*;
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle, render::view::ColorGrading};
+use bevy::{input::gamepad::{GamepadConnection, GamepadConnectionEvent}, prelude::*, sprite::Anchor, window::WindowFocused};
+use ubsg::{game::{Engine, GameloopStates, FRAME_DURATION}, stats::SPLIT_LINES, master::{secret_grade, SECTION_LEVELS}, modes::GameMode, randomizers::*, replay::{Replay, ReplayPlayer}};
 
 const MINO_SIZE: f32 = 20.0;
 const SMALL_MINO_SIZE: f32 = 10.0;
+const LOCK_OUTLINE_WIDTH: f32 = 2.0;
+const RESUME_COUNTDOWN: u32 = 180; // frames
+const PLAYER_ONE_OFFSET: f32 = -300.0; // boards in versus
+const PLAYER_TWO_OFFSET: f32 = 300.0;
+const HUD_TEXT_X: f32 = -140.0; // relative to playfield, left of the board
 
-pub fn reset_engine(mut commands: Commands){
-    commands.remove_resource::<Engine>();
-    commands.insert_resource::<Engine>(Engine::default());
+type AnyMino = Or<(With<Mino>, With<UImino>)>;
+type BoardVisuals = Or<(With<Playfield>, With<BoardVisual>, With<Mino>, With<UImino>)>;
+type PlayfieldParts<'a> = (&'a mut Playfield, &'a mut PendingInputs, &'a Bindings, &'a Player, Option<&'a mut ReplayRecorder>, Option<&'a mut PlaybackPlayer>);
+type LockDelayTextOnly = (With<LockDelayText>, Without<ScoreText>, Without<PlacementText>);
+type ScoreTextOnly = (With<ScoreText>, Without<LockDelayText>, Without<PlacementText>);
+type PlacementTextOnly = (With<PlacementText>, Without<LockDelayText>, Without<ScoreText>);
+
+fn bevy_color(color: ubsg::game::Color) -> Color {
+    Color::rgba(color.red, color.green, color.blue, color.alpha)
 }
 
-pub fn init_engine(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut engine: ResMut<Engine>,
-    mut old_board: Query<Entity, With<BoardVisual>>,
-    mut next_state: ResMut<NextState<GameloopStates>>,
-    mut game_next_state: ResMut<NextState<GameStates>>,
-    asset_server: Res<AssetServer>,
-) { // despawn old board
-    for board in old_board.iter() {
-        commands.entity(board).despawn();
-    }
-    // spawn board
-    let board = commands.spawn((
-        SpriteBundle {
-            transform: Transform{
-                translation: Vec3 { x: 0.0, y: 0.0, z: -1.0},
-                rotation: Quat::default(),
-                scale: Vec3 { x: 0.5, y: 0.5, z: 1. },
+// m:ss.mmm
+fn format_time(ms: f32) -> String {
+    let ms = ms as u64;
+    format!("{}:{:02}.{:03}", ms / 60000, ms / 1000 % 60, ms % 1000)
+}
+
+fn hud_text(engine: &Engine) -> String {
+    let lines = match (engine.mode.lines_left(&engine.score), engine.mode.garbage_left(&engine.stats)) {
+        (Some(left), _) => format!("{}/{}", engine.score.lines, engine.score.lines + left),
+        (None, Some(left)) => format!("{}\nGarbage left {}", engine.score.lines, left),
+        (None, None) => format!("{}", engine.score.lines),
+    };
+    let time = match engine.mode.frames_left(engine.stats.frames(engine.frame)) {
+        Some(left) => format!("Time left {}", format_time(left as f32 * FRAME_DURATION)),
+        None => format!("Time {}", format_time(engine.stats.time(engine.frame))),
+    };
+    let level = match engine.mode.level_goal(&engine.score) {
+        Some(goal) => format!("{}/{}", engine.score.level, goal),
+        None => format!("{}", engine.score.level),
+    };
+    let mut value = format!(
+        "Score\n{}\nLevel {}\nLines {}\n{}\nPPS {:.2}\nSent {}",
+        engine.score.score, level, lines,
+        time, engine.stats.pieces_per_second(engine.frame), engine.attack_total
+    );
+    if let Some(grade) = engine.mode.grade(&engine.score, &engine.stats, engine.frame) {
+        value += &format!("\nGrade {}", grade);
+    }
+    value
+}
+
+// Player, who topped out, loses. Player, who finished the goal of the mode first, wins
+fn versus_winner(one: &Engine, two: &Engine) -> &'static str {
+    let one_lost = one.state == GameloopStates::GameOver || two.state == GameloopStates::Finished;
+    let two_lost = two.state == GameloopStates::GameOver || one.state == GameloopStates::Finished;
+    match (one_lost, two_lost) {
+        (true, false) => "Player 2 wins",
+        (false, true) => "Player 1 wins",
+        _ => "Draw",
+    }
+}
+
+// Playfield is drawn around its transform, border and texts are its children
+fn spawn_playfield(commands: &mut Commands, asset_server: &AssetServer, engine: Engine, player: Player, bindings: ActionMap, offset: f32) -> Entity {
+    let grid = if engine.board.show_grid { Visibility::Inherited } else { Visibility::Hidden };
+    let text_style = TextStyle {
+        font: asset_server.load("EurostileRound-Regular.ttf"),
+        font_size: 32.0,
+        color: Color::rgb(1.0, 1.0, 1.0),
+    };
+    commands.spawn((
+        SpatialBundle::from_transform(Transform::from_xyz(offset, 0.0, 0.0)),
+        Playfield(engine),
+        PendingInputs::default(),
+        player,
+        Bindings(bindings),
+    ))
+    .with_children( |parent| {
+        parent.spawn((
+            SpriteBundle {
+                transform: Transform{
+                    translation: Vec3 { x: 0.0, y: 0.0, z: -1.0},
+                    rotation: Quat::default(),
+                    scale: Vec3 { x: 0.5, y: 0.5, z: 1. },
+                },
+                texture: asset_server.load("border.png"),
+                sprite: Sprite {
+                    color: Color::Rgba { red: 1.0, green: 1.0, blue: 1.0, alpha: 1.0 },
+                    ..default()
+                },
+                ..default()
             },
-            texture: asset_server.load("border.png"),
-            sprite: Sprite {
-                color: Color::Rgba { red: 1.0, green: 1.0, blue: 1.0, alpha: 1.0 },
+            BoardVisual {},
+        ))
+        .with_children( |parent| {
+            parent.spawn((SpriteBundle {
+                transform: Transform{
+                    translation: Vec3 { x: 0.0, y: 487.0, z: -0.0},
+                    rotation: Quat::default(),
+                    scale: Vec3 { x: 1.0, y: 1.0, z: 1. },
+                },
+                texture: asset_server.load("board.png"),
+                sprite: Sprite {
+                    color: Color::Rgba { red: 1.0, green: 1.0, blue: 1.0, alpha: 1.0 },
+                    ..default()
+                },
+                visibility: grid,
                 ..default()
             },
-            ..default()
-        },
-        BoardVisual {},
-    ))
-    .with_children( |parent| {
-        parent.spawn((SpriteBundle {
-            transform: Transform{
-                translation: Vec3 { x: 0.0, y: 487.0, z: -0.0},
-                rotation: Quat::default(),
-                scale: Vec3 { x: 1.0, y: 1.0, z: 1. },
+        BoardVisual{}));
+        });
+        parent.spawn((
+            Text2dBundle {
+                text: Text::from_section("Score\n0", text_style.clone()).with_alignment(TextAlignment::Right),
+                text_anchor: Anchor::TopRight,
+                transform: Transform::from_xyz(HUD_TEXT_X, 180.0, 0.0),
+                ..default()
             },
-            texture: asset_server.load("board.png"),
-            sprite: Sprite {
-                color: Color::Rgba { red: 1.0, green: 1.0, blue: 1.0, alpha: 1.0 },
+            ScoreText {},
+        ));
+        parent.spawn((
+            Text2dBundle {
+                text: Text::from_section("", TextStyle { color: Color::rgb(1.0, 0.0, 1.0), ..text_style }).with_alignment(TextAlignment::Right),
+                text_anchor: Anchor::TopRight,
+                transform: Transform::from_xyz(HUD_TEXT_X, -80.0, 0.0),
                 ..default()
             },
-            ..default()
-        },
-    BoardVisual{}));
+            PlacementText {},
+        ));
     })
-    .id();
+    .id()
+}
 
-    // init engine
-    engine.init("ARS", Box::new(Bag::create()));
+fn save_replay(replay: &Replay) {
+    if replay.frames == 0 {
+        return;
+    }
+    let timestamp = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => n.as_secs(),
+        Err(_) => 0,
+    };
+    // several games can end in the same second, seed and counter keep their files apart
+    let mut path = PathBuf::from("replays").join(format!("{}-{}.ron", timestamp, replay.seed));
+    let mut copy = 1;
+    while path.exists() {
+        path = PathBuf::from("replays").join(format!("{}-{}-{}.ron", timestamp, replay.seed, copy));
+        copy += 1;
+    }
+    match replay.save(&path) {
+        Ok(_) => info!("Replay saved to {}", path.display()),
+        Err(error) => error!("Replay was not saved: {}", error),
+    }
+}
+
+pub fn init_engine(
+    mut commands: Commands,
+    old_playfields: Query<Entity, With<Playfield>>,
+    mut game_next_state: ResMut<NextState<GameStates>>,
+    playback: Option<Res<Playback>>,
+    settings: Res<GameSettings>,
+    controls: Res<Controls>,
+    asset_server: Res<AssetServer>,
+) { // despawn old boards
+    for playfield in old_playfields.iter() {
+        commands.entity(playfield).despawn_recursive();
+    }
+
+    // init engines
+    match playback {
+        Some(playback) => match playback.0.create_engine() {
+            Ok(replay_engine) => {
+                let playfield = spawn_playfield(&mut commands, &asset_server, replay_engine, Player(0), controls.solo.clone(), 0.0);
+                commands.entity(playfield).insert(PlaybackPlayer(ReplayPlayer::create(playback.0.clone())));
+            },
+            Err(error) => {
+                error!("Can't play replay: {}", error);
+                commands.remove_resource::<Playback>();
+                game_next_state.set(GameStates::Menu);
+                return;
+            },
+        },
+        None => {
+            let seed = random_seed();
+            if settings.versus {
+                let seeds = [seed, if settings.same_seed { seed } else { random_seed() }];
+                // garbage from opponent can't be reproduced by replay, so versus isn't recorded
+                for (player, (bindings, offset)) in [(&controls.player_one, PLAYER_ONE_OFFSET), (&controls.player_two, PLAYER_TWO_OFFSET)].into_iter().enumerate() {
+                    let engine = engine_from_settings(&settings, seeds[player], Some(player));
+                    spawn_playfield(&mut commands, &asset_server, engine, Player(player), bindings.clone(), offset);
+                }
+            } else {
+                let engine = engine_from_settings(&settings, seed, None);
+                let recorder = ReplayRecorder(Replay::create(&engine));
+                let playfield = spawn_playfield(&mut commands, &asset_server, engine, Player(0), controls.solo.clone(), 0.0);
+                commands.entity(playfield).insert(recorder);
+            }
+        },
+    }
     game_next_state.set(GameStates::Gameplay);
-    next_state.set(GameloopStates::Falling);
+}
+
+// Player is None in solo game
+fn engine_from_settings(settings: &GameSettings, seed: u64, player: Option<usize>) -> Engine {
+    let (rotation_system, randomizer) = settings.mode.required_rules().unwrap_or((&settings.user.rotation_system, &settings.user.randomizer));
+    let randomizer = create_randomizer(randomizer, seed).unwrap_or_else(|| Box::new(Bag::create(seed)));
+    let mut engine = Engine::default();
+    settings.user.apply(&mut engine, player);
+    engine.difficulty.hold_allowed = settings.hold;
+    engine.attack_table = settings.attack_table;
+    engine.init(rotation_system, randomizer, settings.mode.scoring_table(settings.scoring), settings.mode);
+    engine
 }
 
 pub fn draw_board(
     mut commands: Commands,
-    engine: Res<Engine>,
-    all_minos: Query<Entity, With<Mino>>,
+    playfields: Query<(Entity, &Playfield), Changed<Playfield>>,
+    all_minos: Query<(Entity, &Parent), With<Mino>>,
     asset_server: Res<AssetServer>,
 ) {
-    if !engine.is_changed(){return;}
-    for mino in all_minos.iter() {
-        commands.entity(mino).despawn();
+    for (entity, playfield) in playfields.iter() {
+        for (mino, parent) in all_minos.iter() {
+            if parent.get() == entity {
+                commands.entity(mino).despawn_recursive();
+            }
+        }
+        commands.entity(entity).with_children(|parent| spawn_board_minos(parent, playfield, &asset_server));
     }
+}
+
+fn spawn_board_minos(parent: &mut ChildBuilder, engine: &Engine, asset_server: &AssetServer) {
     let mut x: f32 = 0.0;
     let mut y: f32 = 0.0;
 
     // draw board
     for row in &engine.board.board {
         for mino in row {
-            match mino {
-                Some(mino) => {
-                    commands.spawn((
+            if let Some(mino) = mino {
+                let position = Vec2::new(
+                    x * MINO_SIZE - (engine.board.width as f32) / 2.0 * MINO_SIZE + MINO_SIZE / 2.0,
+                    y * MINO_SIZE - (engine.board.height as f32) / 2.0 * MINO_SIZE + MINO_SIZE / 2.0,
+                );
+                // white outline behind just locked minos of invisible stack
+                if engine.mino_just_locked(mino) {
+                    parent.spawn((
                         SpriteBundle {
-                            transform: Transform::from_xyz(
-                                x * MINO_SIZE - (engine.board.width as f32) / 2.0 * MINO_SIZE
-                                    + MINO_SIZE / 2.0,
-                                y * MINO_SIZE - (engine.board.height as f32) / 2.0 * MINO_SIZE
-                                    + MINO_SIZE / 2.0,
-                                0.0,
-                            ),
-                            texture: asset_server.load("default_mino.png"),
+                            transform: Transform::from_xyz(position.x, position.y, -0.5),
                             sprite: Sprite {
-                                color: mino.color,
+                                color: Color::WHITE,
                                 custom_size: Some(Vec2 {
-                                    x: MINO_SIZE,
-                                    y: MINO_SIZE,
+                                    x: MINO_SIZE + LOCK_OUTLINE_WIDTH * 2.0,
+                                    y: MINO_SIZE + LOCK_OUTLINE_WIDTH * 2.0,
                                 }),
                                 ..default()
                             },
                             ..default()
                         },
-                        *mino,
+                        Mino {},
                     ));
                 }
-                None => {}
-            };
-            x += 1.0;
-        }
-        x = 0.0;
-        y += 1.0;
-    }
-
-    //draw current piece
-    match engine.current_piece.as_ref() {
-        Some(piece) => {
-            x = piece.position.0 as f32;
-            y = piece.position.1 as f32;
-            for mino in &engine.rotation_system.pieces[piece.id][piece.rotation] {
-                commands.spawn((
+                parent.spawn((
                     SpriteBundle {
-                        transform: Transform::from_xyz(
-                            x * MINO_SIZE - (engine.board.width as f32) / 2.0 * MINO_SIZE
-                                + MINO_SIZE / 2.0
-                                + mino.0 as f32 * MINO_SIZE,
-                            y * MINO_SIZE - (engine.board.height as f32) / 2.0 * MINO_SIZE
-                                + MINO_SIZE / 2.0
-                                + mino.1 as f32 * MINO_SIZE,
-                            1.0,
-                        ),
+                        transform: Transform::from_xyz(position.x, position.y, 0.0),
                         texture: asset_server.load("default_mino.png"),
                         sprite: Sprite {
-                            color: engine.rotation_system.colours[piece.id],
+                            // rows waiting for line clear delay flash white
+                            color: if engine.clearing_lines.contains(&(y as usize)) {
+                                Color::WHITE
+                            } else {
+                                bevy_color(mino.color).with_a(engine.mino_alpha(mino))
+                            },
                             custom_size: Some(Vec2 {
                                 x: MINO_SIZE,
                                 y: MINO_SIZE,
@@ -143,107 +282,137 @@ pub fn draw_board(
                         },
                         ..default()
                     },
-                    Mino {
-                        color: engine.rotation_system.colours[piece.id],
-                    },
+                    Mino {},
                 ));
-            }
+            };
+            x += 1.0;
+        }
+        x = 0.0;
+        y += 1.0;
+    }
+
+    //draw current piece
+    if let Some(piece) = engine.current_piece.as_ref() {
+        x = piece.position.0 as f32;
+        y = piece.position.1 as f32;
+        for mino in &engine.rotation_system.pieces[piece.id][piece.rotation] {
+            parent.spawn((
+                SpriteBundle {
+                    transform: Transform::from_xyz(
+                        x * MINO_SIZE - (engine.board.width as f32) / 2.0 * MINO_SIZE
+                            + MINO_SIZE / 2.0
+                            + mino.0 as f32 * MINO_SIZE,
+                        y * MINO_SIZE - (engine.board.height as f32) / 2.0 * MINO_SIZE
+                            + MINO_SIZE / 2.0
+                            + mino.1 as f32 * MINO_SIZE,
+                        1.0,
+                    ),
+                    texture: asset_server.load("default_mino.png"),
+                    sprite: Sprite {
+                        color: bevy_color(engine.rotation_system.colours[piece.id]),
+                        custom_size: Some(Vec2 {
+                            x: MINO_SIZE,
+                            y: MINO_SIZE,
+                        }),
+                        ..default()
+                    },
+                    ..default()
+                },
+                Mino {},
+            ));
         }
-        None => {}
     }
 
     // draw hold
-    match engine.hold.as_ref() {
-        Some(piece) => {
+    if let Some(piece) = engine.hold.as_ref() {
+        for mino in &engine.rotation_system.pieces[piece.id][piece.rotation] {
+            parent.spawn((
+                SpriteBundle {
+                    transform: Transform::from_xyz(
+                        0.0 * MINO_SIZE - (engine.board.width as f32) / 2.0 * MINO_SIZE
+                            + MINO_SIZE / 2.0
+                            + mino.0 as f32 * SMALL_MINO_SIZE + engine.rotation_system.spawn_offsets[piece.id].0 as f32 * SMALL_MINO_SIZE,
+                        1.0 * MINO_SIZE
+                            + (engine.board.height as f32) / 2.0 * MINO_SIZE
+                            + MINO_SIZE / 2.0
+                            + mino.1 as f32 * SMALL_MINO_SIZE + engine.rotation_system.spawn_offsets[piece.id].1 as f32 * SMALL_MINO_SIZE,
+                        0.0,
+                    ),
+                    texture: asset_server.load("default_mino.png"),
+                    sprite: Sprite {
+                        color: bevy_color(piece.color),
+                        custom_size: Some(Vec2 {
+                            x: SMALL_MINO_SIZE,
+                            y: SMALL_MINO_SIZE,
+                        }),
+                        ..default()
+                    },
+                    ..default()
+                },
+                Mino {},
+            ));
+        }
+    }
+
+    // draw shadow
+    if engine.board.show_shadow {
+        if let Some(piece) = engine.current_piece.as_ref() {
+            x = piece.position.0 as f32;
+            y = engine.lowest_point_under_current_piece() as f32;
             for mino in &engine.rotation_system.pieces[piece.id][piece.rotation] {
-                commands.spawn((
+                parent.spawn((
                     SpriteBundle {
                         transform: Transform::from_xyz(
-                            0.0 * MINO_SIZE - (engine.board.width as f32) / 2.0 * MINO_SIZE
+                            x * MINO_SIZE - (engine.board.width as f32) / 2.0 * MINO_SIZE
                                 + MINO_SIZE / 2.0
-                                + mino.0 as f32 * SMALL_MINO_SIZE + engine.rotation_system.spawn_offsets[piece.id].0 as f32 * SMALL_MINO_SIZE,
-                            1.0 * MINO_SIZE
-                                + (engine.board.height as f32) / 2.0 * MINO_SIZE
+                                + mino.0 as f32 * MINO_SIZE,
+                            y * MINO_SIZE - (engine.board.height as f32) / 2.0 * MINO_SIZE
                                 + MINO_SIZE / 2.0
-                                + mino.1 as f32 * SMALL_MINO_SIZE + engine.rotation_system.spawn_offsets[piece.id].1 as f32 * SMALL_MINO_SIZE,
+                                + mino.1 as f32 * MINO_SIZE,
                             0.0,
                         ),
                         texture: asset_server.load("default_mino.png"),
                         sprite: Sprite {
-                            color: piece.color,
+                            color: Color::Rgba {
+                                red: 1.0,
+                                green: 1.0,
+                                blue: 1.0,
+                                alpha: 0.1,
+                            },
                             custom_size: Some(Vec2 {
-                                x: SMALL_MINO_SIZE,
-                                y: SMALL_MINO_SIZE,
+                                x: MINO_SIZE,
+                                y: MINO_SIZE,
                             }),
                             ..default()
                         },
                         ..default()
                     },
-                    Mino {
-                        color: engine.rotation_system.colours[piece.id],
-                    },
+                    Mino {},
                 ));
             }
         }
-        None => {}
-    }
-
-    // draw shadow
-    if engine.board.show_shadow {
-        match engine.current_piece.as_ref() {
-            Some(piece) => {
-                x = piece.position.0 as f32;
-                y = engine.lowest_point_under_current_piece() as f32;
-                for mino in &engine.rotation_system.pieces[piece.id][piece.rotation] {
-                    commands.spawn((
-                        SpriteBundle {
-                            transform: Transform::from_xyz(
-                                x * MINO_SIZE - (engine.board.width as f32) / 2.0 * MINO_SIZE
-                                    + MINO_SIZE / 2.0
-                                    + mino.0 as f32 * MINO_SIZE,
-                                y * MINO_SIZE - (engine.board.height as f32) / 2.0 * MINO_SIZE
-                                    + MINO_SIZE / 2.0
-                                    + mino.1 as f32 * MINO_SIZE,
-                                0.0,
-                            ),
-                            texture: asset_server.load("default_mino.png"),
-                            sprite: Sprite {
-                                color: Color::Rgba {
-                                    red: 1.0,
-                                    green: 1.0,
-                                    blue: 1.0,
-                                    alpha: 0.1,
-                                },
-                                custom_size: Some(Vec2 {
-                                    x: MINO_SIZE,
-                                    y: MINO_SIZE,
-                                }),
-                                ..default()
-                            },
-                            ..default()
-                        },
-                        Mino {
-                            color: engine.rotation_system.colours[piece.id],
-                        },
-                    ));
-                }
-            }
-            None => {}
-        }
     }
 }
 
 pub fn draw_next(
     mut commands: Commands,
-    engine: Res<Engine>,
-    all_minos: Query<Entity, With<UImino>>,
+    playfields: Query<(Entity, &Playfield), Changed<Playfield>>,
+    all_minos: Query<(Entity, &Parent), With<UImino>>,
     asset_server: Res<AssetServer>,
 ){
-    for mino in all_minos.iter() {
-        commands.entity(mino).despawn();
+    for (entity, playfield) in playfields.iter() {
+        for (mino, parent) in all_minos.iter() {
+            if parent.get() == entity {
+                commands.entity(mino).despawn_recursive();
+            }
+        }
+        commands.entity(entity).with_children(|parent| spawn_next_minos(parent, playfield, &asset_server));
     }
+}
+
+fn spawn_next_minos(parent: &mut ChildBuilder, engine: &Engine, asset_server: &AssetServer) {
 
-    let mut y: f32 = 11.0;
+    let y: f32 = 11.0;
     let mut x: f32 = 0.0;
     // draw next queue
     if engine.board.show_next > 0 {
@@ -252,7 +421,7 @@ pub fn draw_next(
             if drawed == 0 {
                 for tile in &engine.rotation_system.pieces[mino.id][mino.rotation]
                 {
-                    commands.spawn((
+                    parent.spawn((
                         SpriteBundle {
                             transform: Transform::from_xyz(
                                 (x - 1.5) * MINO_SIZE + tile.0 as f32 * MINO_SIZE + engine.rotation_system.spawn_offsets[mino.id].0 as f32 * SMALL_MINO_SIZE,
@@ -261,7 +430,7 @@ pub fn draw_next(
                             ),
                             texture: asset_server.load("default_mino.png"),
                             sprite: Sprite {
-                                color: engine.rotation_system.colours[mino.id],
+                                color: bevy_color(engine.rotation_system.colours[mino.id]),
                                 custom_size: Some(Vec2 {
                                     x: MINO_SIZE,
                                     y: MINO_SIZE,
@@ -277,7 +446,7 @@ pub fn draw_next(
             } else{
                 for tile in &engine.rotation_system.pieces[mino.id][mino.rotation]
                 {
-                    commands.spawn((
+                    parent.spawn((
                         SpriteBundle {
                             transform: Transform::from_xyz(
                                 (x - 1.5) * SMALL_MINO_SIZE + tile.0 as f32 * SMALL_MINO_SIZE,
@@ -286,7 +455,7 @@ pub fn draw_next(
                             ),
                             texture: asset_server.load("default_mino.png"),
                             sprite: Sprite {
-                                color: engine.rotation_system.colours[mino.id],
+                                color: bevy_color(engine.rotation_system.colours[mino.id]),
                                 custom_size: Some(Vec2 {
                                     x: SMALL_MINO_SIZE,
                                     y: SMALL_MINO_SIZE,
@@ -309,194 +478,310 @@ pub fn draw_next(
 
 }
 
+// Restart and pause can be pressed by any player
+fn any_just_pressed(devices: &InputDevices, bindings: &Query<&Bindings>, action: Action) -> bool {
+    bindings.iter().any(|bindings| bindings.0.just_pressed(devices, action))
+}
+
 pub fn receive_input(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut engine: ResMut<Engine>,
-    state: Res<State<GameloopStates>>,
-    mut next_state: ResMut<NextState<GameloopStates>>,
+    devices: InputDevices,
+    mut playfields: Query<(&Bindings, &mut PendingInputs)>,
     mut game_next_state: ResMut<NextState<GameStates>>,
-    mut commands: Commands
+    recorders: Query<&ReplayRecorder>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::R) && state.get() != &GameloopStates::Init {
-        reset_engine(commands);
-        next_state.set(GameloopStates::Init);
+    if playfields.iter().any(|(bindings, _)| bindings.0.just_pressed(&devices, Action::Restart)) {
+        for recorder in recorders.iter() {
+            save_replay(&recorder.0);
+        }
+        game_next_state.set(GameStates::Init);
     }
-    if keyboard_input.any_just_pressed([KeyCode::Up, KeyCode::X])
-        && state.get() == &GameloopStates::Falling
-    {
-        engine.rotate_current_piece(1);
+    for (bindings, mut pending_inputs) in playfields.iter_mut() {
+        pending_inputs.0.merge(&bindings.0.inputs(&devices));
     }
-    if keyboard_input.just_pressed(KeyCode::Z) && state.get() == &GameloopStates::Falling {
-        engine.rotate_current_piece(-1);
+}
+
+pub fn pause_game(
+    devices: InputDevices,
+    bindings: Query<&Bindings>,
+    mut focus_events: EventReader<WindowFocused>,
+    mut connection_events: EventReader<GamepadConnectionEvent>,
+    mut game_next_state: ResMut<NextState<GameStates>>,
+) {
+    let focus_lost = focus_events.read().any(|event| !event.focused);
+    // someone's controller fell off, game waits until it is plugged back
+    let gamepad_lost = connection_events.read().any(|event| event.connection == GamepadConnection::Disconnected);
+    if any_just_pressed(&devices, &bindings, Action::Pause) || focus_lost || gamepad_lost {
+        game_next_state.set(GameStates::Pause);
     }
-    if keyboard_input.just_pressed(KeyCode::C) && state.get() == &GameloopStates::Falling {
-        engine.hold_current_piece();
+}
+
+pub fn receive_input_on_pause(
+    devices: InputDevices,
+    bindings: Query<&Bindings>,
+    mut game_next_state: ResMut<NextState<GameStates>>,
+    recorders: Query<&ReplayRecorder>,
+) {
+    if any_just_pressed(&devices, &bindings, Action::Pause) {
+        game_next_state.set(GameStates::Countdown);
     }
-    if keyboard_input.just_pressed(KeyCode::Left) {
-        if state.get() == &GameloopStates::Falling {
-            engine.move_current_piece((-1, 0));
+    if any_just_pressed(&devices, &bindings, Action::Restart) {
+        for recorder in recorders.iter() {
+            save_replay(&recorder.0);
         }
-        engine.handling.movement_key_pressed(true, false)
+        game_next_state.set(GameStates::Init);
     }
-    if keyboard_input.just_pressed(KeyCode::Right) {
-        if state.get() == &GameloopStates::Falling {
-            engine.move_current_piece((1, 0));
-        }
-        engine.handling.movement_key_pressed(false, true)
+}
+
+pub fn hide_board(
+    mut commands: Commands,
+    all_minos: Query<Entity, AnyMino>,
+    mut pause_text: Query<&mut Text, With<PauseText>>,
+) {
+    for mino in all_minos.iter() {
+        commands.entity(mino).despawn_recursive();
     }
-    if keyboard_input.just_released(KeyCode::Left) {
-        engine.handling.movement_key_released(true, false)
+    for mut text in pause_text.iter_mut() {
+        text.sections[0].value = String::from("PAUSED");
     }
-    if keyboard_input.just_released(KeyCode::Right) {
-        engine.handling.movement_key_released(false, true)
+}
+
+pub fn start_resume_countdown(mut commands: Commands, mut playfields: Query<&mut Playfield>) {
+    commands.insert_resource(ResumeCountdown(RESUME_COUNTDOWN));
+    // boards were hidden during pause
+    for mut playfield in playfields.iter_mut() {
+        playfield.set_changed();
     }
-    if keyboard_input.just_pressed(KeyCode::Down) {
-        engine.handling.sdf_active = true;
+}
+
+// Restart from pause doesn't go through the countdown, which clears the text otherwise
+pub fn clear_pause_text(mut pause_text: Query<&mut Text, With<PauseText>>) {
+    for mut text in pause_text.iter_mut() {
+        text.sections[0].value = String::new();
     }
-    if keyboard_input.just_released(KeyCode::Down) {
-        engine.handling.sdf_active = false;
+}
+
+pub fn run_resume_countdown(
+    mut countdown: ResMut<ResumeCountdown>,
+    mut pause_text: Query<&mut Text, With<PauseText>>,
+    mut game_next_state: ResMut<NextState<GameStates>>,
+) {
+    if countdown.0 > 0 {
+        countdown.0 -= 1;
     }
-    if keyboard_input.just_pressed(KeyCode::Space) && state.get() == &GameloopStates::Falling {
-        engine.sonic_drop();
-        engine.lock_current_piece();
-        next_state.set(GameloopStates::AfterLocking);
+    let value = if countdown.0 == 0 {
+        game_next_state.set(GameStates::Gameplay);
+        String::new()
+    } else {
+        format!("{}", (countdown.0 - 1) * 3 / RESUME_COUNTDOWN + 1)
+    };
+    for mut text in pause_text.iter_mut() {
+        text.sections[0].value = value.clone();
     }
 }
 
-pub fn receive_input_on_game_over(
-    keyboard_input: Res<Input<KeyCode>>,
-    state: Res<State<GameloopStates>>,
-    mut next_state: ResMut<NextState<GameloopStates>>,
-    mut commands: Commands
-){
-    if keyboard_input.just_pressed(KeyCode::R) && state.get() != &GameloopStates::Init {
-        reset_engine(commands);
-        next_state.set(GameloopStates::Init);
-    } 
+// Pads are picked up by action maps as soon as they are connected, that only tells about it
+pub fn log_gamepad_connections(mut connection_events: EventReader<GamepadConnectionEvent>) {
+    for event in connection_events.read() {
+        match &event.connection {
+            GamepadConnection::Connected(info) => info!("Gamepad {} connected: {}", event.gamepad.id, info.name),
+            GamepadConnection::Disconnected => info!("Gamepad {} disconnected", event.gamepad.id),
+        }
+    }
 }
 
-pub fn das_and_arr(mut engine: ResMut<Engine>, time: Res<Time>, state: Res<State<GameloopStates>>) {
-    let direction = engine.handling.movement_tick(time.delta_seconds() * 1000.0);
-    if state.get() == &GameloopStates::Falling {
-        engine.move_current_piece((direction, 0));
+pub fn receive_input_on_game_over(
+    mut commands: Commands,
+    devices: InputDevices,
+    bindings: Query<&Bindings>,
+    mut game_next_state: ResMut<NextState<GameStates>>,
+){
+    if any_just_pressed(&devices, &bindings, Action::Restart) {
+        game_next_state.set(GameStates::Init);
+    } else if devices.keyboard.just_pressed(KeyCode::Escape) {
+        // replay is watched once, Start from the menu begins a new game.
+        // Not done on entering the menu: it's entered on startup too, right before playback starts
+        commands.remove_resource::<Playback>();
+        game_next_state.set(GameStates::Menu);
     }
 }
 
-pub fn gameloop(
-    clocks: Res<Time<Fixed>>,
-    mut lock_delay_text: Query<&mut Text, With<LockDelayText>>,
-    mut engine: ResMut<Engine>,
-    mut next_state: ResMut<NextState<GameloopStates>>,
+pub fn spawn_results(
+    mut commands: Commands,
+    playfields: Query<(&Playfield, &Player, &Bindings)>,
+    asset_server: Res<AssetServer>,
 ) {
-    //info!("{:?}", clocks);
-    match engine.current_piece {
-        Some(piece) => {
-            if engine.handling.sdf_active {
-                engine.g += engine.difficulty.gravity * engine.handling.sdf;
-            } else {
-                engine.g += engine.difficulty.gravity;
-            }
-            let mut gravity_tick_happend = false;
-            while engine.g >= 1.0 {
-                engine.move_current_piece((0, -1));
-                engine.g -= 1.0;
-                gravity_tick_happend = true;
-            }
-            let previos_lock_delay_active = engine.lock_delay_active;
-            engine.lock_delay_active =
-                !engine.position_is_valid((piece.position.0, piece.position.1 - 1), piece.rotation);
-            if engine.lock_delay_active {
-                match engine.rotation_system.lock_delay_mode {
-                    LockDelayMode::Disabled => {
-                        engine.need_to_lock = true;
-                    }
-                    LockDelayMode::Gravity => {
-                        if gravity_tick_happend && previos_lock_delay_active {
-                            engine.need_to_lock = true;
-                        }
-                    }
-                    LockDelayMode::ResetOnYChange => {
-                        engine.lock_delay -= 1;
-                    }
-                    LockDelayMode::ResetOnMovementLimited => {
-                        engine.lock_delay -= 1;
-                    }
-                    LockDelayMode::ResetOnMovement => {
-                        engine.lock_delay -= 1;
-                    }
-                }
-            } else {
-                if previos_lock_delay_active {
-                    match engine.rotation_system.lock_delay_mode {
-                        LockDelayMode::Disabled => {}
-                        LockDelayMode::Gravity => {}
-                        LockDelayMode::ResetOnYChange => {
-                            engine.lock_delay = engine.difficulty.lock_delay;
-                            if engine.lock_delay_resets == 0 {
-                                engine.need_to_lock = true;
-                            } else {
-                                engine.lock_delay_resets -= 1;
-                            }
-                        }
-                        LockDelayMode::ResetOnMovementLimited => {
-                            engine.lock_delay = engine.difficulty.lock_delay;
-                        }
-                        LockDelayMode::ResetOnMovement => {
-                            engine.lock_delay = engine.difficulty.lock_delay;
-                        }
-                    }
-                }
-            }
-            if (engine.lock_delay < 1 || engine.need_to_lock)
-                && !engine
-                    .position_is_valid((piece.position.0, piece.position.1 - 1), piece.rotation)
-            {
-                engine.lock_current_piece();
-                next_state.set(GameloopStates::AfterLocking);
-            }
+    let mut players: Vec<(&Playfield, &Player, &Bindings)> = playfields.iter().collect();
+    players.sort_by_key(|(_, player, _)| player.0);
+    let (engine, bindings) = match players.first() {
+        Some((playfield, _, bindings)) => (&playfield.0, &bindings.0),
+        None => return,
+    };
+    let opponent = players.get(1).map(|(playfield, ..)| &playfield.0);
+    let mut value = String::new();
+    if let Some(opponent) = opponent {
+        value += &format!("{}\n\nPlayer 1\n", versus_winner(engine, opponent));
+    }
+    value += &format!(
+        "{}\nTime {}\nPieces {}\nPPS {:.2}\nScore {}\nLines {}\n",
+        engine.mode.name(),
+        format_time(engine.stats.time(engine.frame)),
+        engine.stats.pieces,
+        engine.stats.pieces_per_second(engine.frame),
+        engine.score.score,
+        engine.score.lines
+    );
+    for (name, count) in ["Singles", "Doubles", "Triples", "Quads"].iter().zip(engine.score.clears) {
+        value += &format!("\n{} {}", name, count);
+    }
+    value += &format!("\nT-spins {}\nSent {}\n", engine.score.spin_clears, engine.attack_total);
+    if let GameMode::Survival { .. } = engine.mode {
+        value += &format!("\nSurvived {}\nGarbage risen {}\n", format_time(engine.stats.time(engine.frame)), engine.garbage.added);
+    }
+    if engine.mode.garbage_left(&engine.stats).is_some() {
+        value += &format!("\nGarbage cleared {}\nPieces per garbage line {:.2}\n", engine.stats.garbage_cleared, engine.stats.pieces_per_garbage_line());
+    }
+    if let Some(grade) = engine.mode.grade(&engine.score, &engine.stats, engine.frame) {
+        value += &format!("\nGrade {}  Level {}\n", grade, engine.score.level);
+        if let Some(secret_grade) = secret_grade(&engine.board) {
+            value += &format!("Secret grade {}\n", secret_grade);
+        }
+        for (i, (frames, _)) in engine.stats.sections.iter().enumerate() {
+            value += &format!("\n{}  {}", (i as u32 + 1) * SECTION_LEVELS, format_time(*frames as f32 * FRAME_DURATION));
         }
-        None => {}
     }
-    for mut text in lock_delay_text.iter_mut() {
-        text.sections[0].value = format!(
-            "{}; {}",
-            engine.lock_delay_resets, engine.lock_delay
+    for (i, split) in engine.stats.splits.iter().enumerate() {
+        value += &format!("\n{}L  {}", (i as u32 + 1) * SPLIT_LINES, format_time(*split as f32 * FRAME_DURATION));
+    }
+    if let Some(opponent) = opponent {
+        value += &format!(
+            "\n\nPlayer 2\nPieces {}\nPPS {:.2}\nLines {}\nSent {}",
+            opponent.stats.pieces, opponent.stats.pieces_per_second(opponent.frame), opponent.score.lines, opponent.attack_total
         );
     }
+    value += &format!("\n\n{} - retry, Escape - menu", bindings.describe(Action::Restart));
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(if opponent.is_some() { 5.0 } else { 20.0 }),
+                left: Val::Percent(if opponent.is_some() { 42.0 } else { 65.0 }),
+                ..default()
+            },
+            text: Text {
+                sections: vec![TextSection::new(
+                    value,
+                    TextStyle {
+                        font: asset_server.load("EurostileRound-Regular.ttf"),
+                        font_size: 32.0,
+                        color: Color::rgb(1.0, 1.0, 1.0),
+                    },
+                )],
+                alignment: TextAlignment::Left,
+                ..default()
+            },
+            ..default()
+        },
+        ResultsText {},
+    ));
 }
 
-pub fn after_locking_routine(
-    mut engine: ResMut<Engine>,
-    mut next_state: ResMut<NextState<GameloopStates>>,
-) {
-    engine.board.clear_full_lines();
-    next_state.set(GameloopStates::Spawn);
-
+pub fn despawn_results(mut commands: Commands, results_query: Query<Entity, With<ResultsText>>) {
+    for entity in results_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
 }
 
-pub fn run_spawn_delay(
-    mut engine: ResMut<Engine>,
-    mut next_state: ResMut<NextState<GameloopStates>>,
-){
-    if engine.spawn_delay > 0 {
-        engine.spawn_delay -= 1; 
-    }else{
-        engine.spawn_delay = engine.difficulty.spawn_delay;
-        next_state.set(GameloopStates::Falling);
+pub fn despawn_board(
+    mut commands: Commands,
+    board_visuals: Query<Entity, BoardVisuals>,
+) {
+    for entity in board_visuals.iter() {
+        commands.entity(entity).despawn_recursive();
     }
 }
 
-pub fn spawn_routine(
-    mut engine: ResMut<Engine>,
-    mut next_state: ResMut<NextState<GameloopStates>>,
+pub fn gameloop(
+    devices: InputDevices,
+    mut playfields: Query<PlayfieldParts>,
+    mut lock_delay_text: Query<&mut Text, LockDelayTextOnly>,
+    mut score_text: Query<(&Parent, &mut Text), ScoreTextOnly>,
+    mut placement_text: Query<(&Parent, &mut Text), PlacementTextOnly>,
     mut game_next_state: ResMut<NextState<GameStates>>,
 ) {
-    engine.lock_delay = engine.difficulty.lock_delay;
-    engine.lock_delay_resets = engine.difficulty.lock_delay_resets;
-    engine.lock_delay_active = false;
-    if engine.spawn_sequence() {
-        next_state.set(GameloopStates::Falling);
-    } else {
-        game_next_state.set(GameStates::GameOver);
+    let mut sent = vec![];
+    for (mut playfield, mut pending_inputs, bindings, _, recorder, player) in playfields.iter_mut() {
+        let inputs = match player {
+            Some(mut player) => {
+                if player.0.is_finished(playfield.frame) {
+                    game_next_state.set(GameStates::GameOver);
+                    return;
+                }
+                player.0.inputs(playfield.frame)
+            },
+            None => {
+                let inputs = pending_inputs.0;
+                // keys that are still held must not look like released in the next frame
+                pending_inputs.0 = bindings.0.inputs(&devices);
+                inputs
+            },
+        };
+        if let Some(mut recorder) = recorder {
+            recorder.0.record(playfield.frame, &inputs);
+        }
+        playfield.step(&inputs);
+        sent.push(take(&mut playfield.outgoing_garbage));
+    }
+    // everyone receives garbage sent by others
+    let total: u32 = sent.iter().sum();
+    for ((mut playfield, ..), lines) in playfields.iter_mut().zip(&sent) {
+        playfield.receive_garbage(total - lines);
+    }
+    if playfields.iter().any(|(playfield, ..)| playfield.is_over()) {
+        let versus = playfields.iter().count() > 1;
+        let mut finished = false;
+        for (playfield, _, _, _, recorder, _) in playfields.iter() {
+            if let Some(recorder) = recorder {
+                save_replay(&recorder.0);
+            }
+            finished |= playfield.state == GameloopStates::Finished || playfield.mode.results_on_top_out();
+        }
+        if versus || finished {
+            game_next_state.set(GameStates::Results);
+        } else {
+            game_next_state.set(GameStates::GameOver);
+        }
+    }
+    for (playfield, _, _, player, ..) in playfields.iter() {
+        if player.0 != 0 {
+            continue;
+        }
+        for mut text in lock_delay_text.iter_mut() {
+            text.sections[0].value = format!(
+                "{}; {}",
+                playfield.lock_delay_resets, playfield.lock_delay
+            );
+        }
+    }
+    for (parent, mut text) in score_text.iter_mut() {
+        if let Ok((playfield, ..)) = playfields.get(parent.get()) {
+            text.sections[0].value = hud_text(playfield);
+        }
+    }
+    for (parent, mut text) in placement_text.iter_mut() {
+        let engine = match playfields.get(parent.get()) {
+            Ok((playfield, ..)) => &playfield.0,
+            Err(_) => continue,
+        };
+        let mut value = String::from(engine.last_placement.name());
+        if engine.combo > 1 {
+            value += &format!("\n{} COMBO", engine.combo - 1);
+        }
+        if engine.back_to_back > 1 {
+            value += &format!("\nB2B x{}", engine.back_to_back - 1);
+        }
+        if engine.last_attack > 0 {
+            value += &format!("\n+{}", engine.last_attack);
+        }
+        text.sections[0].value = value;
     }
 }
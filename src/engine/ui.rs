@@ -1,4 +1,4 @@
-use super::components::{HUD, LockDelayText};
+use super::components::{Hud, LockDelayText, PauseText};
 use bevy::prelude::*;
 
 pub fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
@@ -20,7 +20,7 @@ pub fn build_hud(commands: &mut Commands, asset_server: &Res<AssetServer>) -> En
                 },
                 ..default()
             },
-            HUD {},
+            Hud {},
         ))
         .with_children(|parent| {
             // Enemy Text
@@ -49,12 +49,36 @@ pub fn build_hud(commands: &mut Commands, asset_server: &Res<AssetServer>) -> En
                 },
                 LockDelayText {},
             ));
+            parent.spawn((
+                TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Percent(45.0),
+                        left: Val::Percent(45.0),
+                        ..default()
+                    },
+                    text: Text {
+                        sections: vec![TextSection::new(
+                            "",
+                            TextStyle {
+                                font: asset_server.load("EurostileRound-Regular.ttf"),
+                                font_size: 64.0,
+                                color: Color::rgb(1.0, 1.0, 1.0),
+                            },
+                        )],
+                        alignment: TextAlignment::Center,
+                        ..default()
+                    },
+                    ..default()
+                },
+                PauseText {},
+            ));
         })
         .id();
     hud_entity
 }
 
-pub fn despawn_hud(mut commands: Commands, hud_query: Query<Entity, With<HUD>>) {
+pub fn despawn_hud(mut commands: Commands, hud_query: Query<Entity, With<Hud>>) {
     for entity in hud_query.iter() {
         commands.entity(entity).despawn_recursive();
     }
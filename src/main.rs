@@ -1,16 +1,29 @@
 mod engine;
+use std::{env, path::Path};
+
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::prelude::*;
-use engine::UBSGEngine;
+use engine::{UBSGEngine, Playback};
+use ubsg::replay::Replay;
 
 fn main() {
-    App::new()
+    let mut app = App::new();
+    // `--replay <file>` plays the replay back instead of starting a new game
+    let args: Vec<String> = env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--replay") {
+        match args.get(index + 1).map(|path| Replay::load(Path::new(path))) {
+            Some(Ok(replay)) => { app.insert_resource(Playback(replay)); },
+            Some(Err(error)) => eprintln!("Can't load replay: {}", error),
+            None => eprintln!("--replay needs a path to replay file"),
+        }
+    }
+    app
         //.insert_resource(ClearColor(Color::DARK_GRAY))
         .insert_resource(Msaa::Off)
         //.add_plugins(DefaultPlugins)
         .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
         .add_plugins(LogDiagnosticsPlugin::default())
-        .add_plugins(FrameTimeDiagnosticsPlugin::default())
+        .add_plugins(FrameTimeDiagnosticsPlugin)
         .add_plugins(UBSGEngine)
         .add_systems(Startup, startup)
         //.add_systems(Update, gameloop)
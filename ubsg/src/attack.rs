@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+
+use crate::spins::Placement;
+
+// Lines sent for combo, combo_bonus[n] is for n-th consecutive line clear after the first one
+const GUIDELINE_COMBO: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+const TETRIS_99_COMBO: [u32; 11] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum AttackTable {
+    #[default]
+    Guideline, // 0/1/2/4, T-spins 2/4/6, +1 for B2B, combo table
+    Tetris99, // same base with faster combo table
+    TETRIO // same base, B2B bonus grows with the chain, combo multiplies attack
+}
+
+pub const ATTACK_TABLES: [AttackTable; 3] = [AttackTable::Guideline, AttackTable::Tetris99, AttackTable::TETRIO];
+
+impl AttackTable {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AttackTable::Guideline => "Guideline",
+            AttackTable::Tetris99 => "Tetris 99",
+            AttackTable::TETRIO => "TETR.IO",
+        }
+    }
+
+    fn base_attack(lines: usize, placement: Placement) -> u32 {
+        match placement {
+            Placement::Regular => match lines {
+                0 | 1 => 0,
+                2 => 1,
+                3 => 2,
+                _ => 4,
+            },
+            Placement::TSpinMini => match lines {
+                0 | 1 => 0,
+                _ => 1,
+            },
+            Placement::TSpin => lines.min(3) as u32 * 2,
+        }
+    }
+
+    // combo - consecutive line clears after the first one,
+    // back_to_back - consecutive quads and spins after the first one
+    pub fn attack(&self, lines: usize, placement: Placement, combo: u32, back_to_back: u32) -> u32 {
+        if lines == 0 {
+            return 0;
+        }
+        let base = AttackTable::base_attack(lines, placement);
+        match self {
+            AttackTable::Guideline => {
+                base + back_to_back.min(1) + GUIDELINE_COMBO[(combo as usize).min(GUIDELINE_COMBO.len() - 1)]
+            },
+            AttackTable::Tetris99 => {
+                base + back_to_back.min(1) + TETRIS_99_COMBO[(combo as usize).min(TETRIS_99_COMBO.len() - 1)]
+            },
+            AttackTable::TETRIO => {
+                let mut attack = base as f32;
+                if back_to_back > 0 {
+                    attack += (1.0 + (back_to_back as f32 * 0.8).ln_1p()).floor();
+                }
+                if base == 0 && combo >= 2 {
+                    attack = (combo as f32 * 1.25).ln_1p();
+                } else {
+                    attack *= 1.0 + combo as f32 * 0.25;
+                }
+                attack.floor() as u32
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_attack() {
+        let table = AttackTable::Guideline;
+        assert_eq!(table.attack(0, Placement::TSpin, 0, 0), 0);
+        assert_eq!(table.attack(1, Placement::Regular, 0, 0), 0);
+        assert_eq!(table.attack(2, Placement::Regular, 0, 0), 1);
+        assert_eq!(table.attack(4, Placement::Regular, 0, 0), 4);
+        assert_eq!(table.attack(2, Placement::TSpin, 0, 0), 4);
+        assert_eq!(table.attack(3, Placement::TSpin, 0, 0), 6);
+        assert_eq!(table.attack(2, Placement::TSpinMini, 0, 0), 1);
+    }
+
+    #[test]
+    fn back_to_back_adds_one() {
+        assert_eq!(AttackTable::Guideline.attack(4, Placement::Regular, 0, 1), 5);
+        assert_eq!(AttackTable::Guideline.attack(4, Placement::Regular, 0, 5), 5);
+        assert_eq!(AttackTable::Tetris99.attack(2, Placement::TSpin, 0, 3), 5);
+    }
+
+    #[test]
+    fn combo_tables() {
+        assert_eq!(AttackTable::Guideline.attack(1, Placement::Regular, 1, 0), 0);
+        assert_eq!(AttackTable::Guideline.attack(1, Placement::Regular, 3, 0), 1);
+        assert_eq!(AttackTable::Guideline.attack(1, Placement::Regular, 20, 0), 5);
+        assert_eq!(AttackTable::Tetris99.attack(1, Placement::Regular, 1, 0), 1);
+        assert_eq!(AttackTable::Tetris99.attack(1, Placement::Regular, 3, 0), 2);
+        assert_eq!(AttackTable::Tetris99.attack(1, Placement::Regular, 20, 0), 5);
+    }
+
+    #[test]
+    fn tetrio_back_to_back_grows_with_chain() {
+        let table = AttackTable::TETRIO;
+        assert_eq!(table.attack(4, Placement::Regular, 0, 0), 4);
+        assert_eq!(table.attack(4, Placement::Regular, 0, 1), 5);
+        assert_eq!(table.attack(4, Placement::Regular, 0, 3), 6);
+        assert_eq!(table.attack(4, Placement::Regular, 0, 8), 7);
+    }
+
+    #[test]
+    fn tetrio_combo_multiplies_attack() {
+        let table = AttackTable::TETRIO;
+        assert_eq!(table.attack(4, Placement::Regular, 2, 0), 6);
+        assert_eq!(table.attack(2, Placement::TSpin, 4, 0), 8);
+        // singles still send something on long combos
+        assert_eq!(table.attack(1, Placement::Regular, 1, 0), 0);
+        assert_eq!(table.attack(1, Placement::Regular, 2, 0), 1);
+        assert_eq!(table.attack(1, Placement::Regular, 8, 0), 2);
+    }
+}
@@ -0,0 +1,948 @@
+use std::mem::{swap, take};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{rotation_systems::{PiecesData, ROTATION_SYSTEMS, LockDelayMode, KICKS_CW, KICKS_CCW, KICKS_180}, randomizers::{Randomizer, Bag}, scoring::{Score, ScoringTable}, spins::{Placement, classify_placement}, modes::GameMode, stats::Stats, garbage::{Garbage, is_garbage_row}, attack::AttackTable};
+
+// Engine is stepped at fixed 60 frames per second, DAS and ARR are counted with that
+pub const FPS: u64 = 60;
+pub const FRAME_DURATION: f32 = 1000.0 / FPS as f32; // ms
+pub const LOCK_FLASH_FRAMES: u64 = 3;
+pub const STACK_FADE_FRAMES: u64 = 30; // how long invisible stack fades out after its delay
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Color {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub alpha: f32
+}
+
+impl Color {
+    pub const RED: Color = Color { red: 1.0, green: 0.0, blue: 0.0, alpha: 1.0 };
+    pub const GREEN: Color = Color { red: 0.0, green: 1.0, blue: 0.0, alpha: 1.0 };
+    pub const CYAN: Color = Color { red: 0.0, green: 1.0, blue: 1.0, alpha: 1.0 };
+    pub const YELLOW: Color = Color { red: 1.0, green: 1.0, blue: 0.0, alpha: 1.0 };
+    pub const ORANGE: Color = Color { red: 1.0, green: 0.65, blue: 0.0, alpha: 1.0 };
+}
+
+#[derive(Clone, Copy)]
+pub struct Mino{
+    pub color: Color,
+    pub locked_at: u64 // frame
+}
+
+#[derive(Clone, Copy)]
+pub struct Piece{
+    pub id: usize,
+    pub color: Color,
+    pub position: (isize, isize),
+    pub rotation: usize,
+    pub last_kick: Option<(usize, usize)> // direction and kick test used by the last successful rotation, None if piece moved after it
+}
+
+impl Piece {
+    pub fn create(pieces_data: &PiecesData, id: usize, board_width: isize, board_height: isize) -> Piece{
+        let final_position = (
+            board_width/2 - 2 + pieces_data.spawn_offsets[id].0,
+            board_height + pieces_data.height_offset + pieces_data.spawn_offsets[id].1
+        );
+        Piece { id, color: pieces_data.colours[id], position: final_position, rotation: 0, last_kick: None }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Difficulty { // that struct pretty much describes rules
+    pub gravity: f32, // G
+    pub lock_delay: u8, // frames
+    pub lock_delay_resets: u8, // times
+    pub spawn_delay: u8, // frames
+    pub line_clear_delay: u8, // frames
+    pub stack_invis: bool,
+    pub stack_invis_delay: u8, // frames before fading, 0 hides minos right after lock flash without fading
+    pub next_allowed: u8, // num of next pieces to preview
+    pub hold_allowed: bool,
+    #[serde(default)]
+    pub garbage_entry_delay: u8, // frames before received garbage can enter the board
+    #[serde(default)]
+    pub garbage_messiness: f32 // 0.0 - hole stays in the same column, 1.0 - random column every row
+}
+
+pub struct Board{
+    pub width: u8,
+    pub height: u8,
+    pub buffer_height: u8,
+    pub show_grid: bool,
+    pub show_shadow: bool,
+    pub show_next: u8,
+    // X axis - from left to right; Y axis - from bottom to top (board[y][x])
+    pub board: Vec<Vec<Option<Mino>>>
+}
+
+impl Board{
+    pub fn create(width: u8, height: u8, buffer_height: u8, show_grid: bool, show_shadow: bool, show_next: u8) -> Board {
+        let board: Vec<Vec<Option<Mino>>> = vec![vec![None; width as usize]; (height+buffer_height) as usize];
+        Board { width, height, buffer_height, show_grid, show_shadow, show_next, board }
+    }
+
+    // Returns how many lines were cleared
+    pub fn clear_full_lines(&mut self) -> usize {
+        let full_lines = self.full_lines();
+        self.remove_lines(&full_lines);
+        full_lines.len()
+    }
+
+    // Indexes of full rows from bottom to top
+    pub fn full_lines(&self) -> Vec<usize> {
+        let mut lines = vec![];
+        for row in 0..self.board.len(){
+            if self.board[row].iter().all(|l| l.is_some()){
+                lines.push(row);
+            }
+        }
+        lines
+    }
+
+    // Inserts rows from the bottom, pushing the stack up.
+    // Returns false if some minos were pushed out of the board
+    pub fn insert_rows(&mut self, rows: Vec<Vec<Option<Mino>>>) -> bool {
+        let count = rows.len();
+        self.board.splice(0..0, rows);
+        let pushed_out = self.board.split_off(self.board.len() - count);
+        pushed_out.iter().all(|row| row.iter().all(|cell| cell.is_none()))
+    }
+
+    // Rows must be sorted from bottom to top, everything above them falls down
+    pub fn remove_lines(&mut self, lines: &[usize]) {
+        for (lines_cleared, row) in lines.iter().enumerate() {
+            self.board.remove(row-lines_cleared);
+            let empty_row: Vec<Option<Mino>> = vec![None; self.width as usize];
+            self.board.push(empty_row);
+        }
+    }
+}
+
+pub struct Handling{
+    pub das: f32, // ms
+    pub arr: f32, // ms
+    pub sdf: f32, // gravity*sdf = soft drop
+    pub das_left: f32, // ms
+    pub arr_left: f32, // ms
+    pub sdf_active: bool,
+    pub active_left: bool,
+    pub active_right: bool,
+    pub direction: i8 // -1 - left, 1 - right, 0 - none
+}
+
+impl Handling {
+    pub fn create(das: f32, arr: f32, sdf: f32) -> Handling{
+        Handling { das, arr, sdf, das_left: das, arr_left: arr, sdf_active:false, active_left: false, active_right: false, direction: 0}
+    }
+
+    pub fn movement_key_pressed(&mut self, left: bool, right: bool){
+        if left {
+            self.active_left = left;
+            self.direction = -1;
+        }
+        if right {
+            self.active_right = right;
+            self.direction = 1;
+        }
+        self.das_left = self.das;
+    }
+
+    pub fn movement_key_released(&mut self, left: bool, right: bool){
+        if left {
+            self.active_left = !left;
+        }
+        if right {
+            self.active_right = !right;
+        }
+        if self.active_left {
+            self.direction = -1;
+        }
+        if self.active_right {
+            self.direction = 1;
+        }
+        if !self.active_left && !self.active_right{
+            self.arr_left = self.arr;
+            self.das_left = self.das;
+            self.direction = 0;
+        }
+    }
+
+    pub fn movement_tick(&mut self, delta: f32) -> i8 {
+        if !self.active_left && !self.active_right {
+            return 0;
+        } 
+        if self.das_left > 0.0 {
+            self.das_left -= delta;
+            if self.das_left <= 0.0 {
+                self.arr_left += self.das_left;
+                self.das_left = 0.0;
+                self.direction
+            }else{
+                0
+            }
+        }else{
+            self.arr_left -= delta;
+            if self.arr_left <= 0.0 {
+                self.arr_left += self.arr;
+                self.direction
+            }else {
+                0
+            }
+        }
+    }
+}
+
+// Buttons held during a frame. Rotations, hold and hard drop act on press, so they
+// should be held for one frame only; movement and soft drop act while held
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Inputs {
+    pub left: bool,
+    pub right: bool,
+    pub soft_drop: bool,
+    pub hard_drop: bool,
+    pub rotate_cw: bool,
+    pub rotate_ccw: bool,
+    pub hold: bool,
+    #[serde(default)]
+    pub sonic_drop: bool,
+    #[serde(default)]
+    pub rotate_180: bool
+}
+
+impl Inputs {
+    // Keeps every button that was held in any of merged frames
+    pub fn merge(&mut self, other: &Inputs) {
+        self.left |= other.left;
+        self.right |= other.right;
+        self.soft_drop |= other.soft_drop;
+        self.hard_drop |= other.hard_drop;
+        self.rotate_cw |= other.rotate_cw;
+        self.rotate_ccw |= other.rotate_ccw;
+        self.hold |= other.hold;
+        self.sonic_drop |= other.sonic_drop;
+        self.rotate_180 |= other.rotate_180;
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Hash)]
+pub enum GameloopStates {
+    #[default]
+    Init,
+    Spawn,
+    Falling,
+    AfterLocking,
+    LineClear,
+    GameOver, // topped out
+    Finished // goal of the mode was reached
+}
+
+pub struct Engine {
+    pub current_piece: Option<Piece>,
+    pub board: Board,
+    pub handling: Handling,
+    pub difficulty: Difficulty,
+    pub rotation_system: PiecesData,
+    pub rotation_system_name: String,
+    pub randomizer: Box<dyn Randomizer + Sync + Send>,
+    pub next_queue: Vec<Piece>,
+    pub hold: Option<Piece>,
+    pub can_hold: bool,
+    pub g: f32,
+    pub lock_delay: u8,
+    pub lock_delay_resets: u8,
+    pub lock_delay_active: bool,
+    pub spawn_delay: u8,
+    pub line_clear_delay: u8,
+    pub clearing_lines: Vec<usize>, // full rows, that wait for line clear delay to end
+    pub need_to_lock: bool, // when lock resets ended
+    pub state: GameloopStates,
+    pub previous_inputs: Inputs,
+    pub frame: u64, // frames stepped since init
+    pub score: Score,
+    pub stats: Stats,
+    pub last_placement: Placement,
+    pub mode: GameMode,
+    pub last_lines_cleared: usize,
+    pub combo: u32, // consecutive pieces, that cleared lines
+    pub back_to_back: u32, // consecutive quads and spins, not broken by other line clears
+    pub garbage: Garbage,
+    pub attack_table: AttackTable,
+    pub last_attack: u32, // lines of attack from the last locked piece, cancelled ones included
+    pub attack_total: u32,
+    pub outgoing_garbage: u32, // attack left after cancelling, waits to be sent to opponent
+}
+
+impl Default for Engine {
+    fn default() -> Engine {
+        Engine {
+            current_piece: None,
+            board: Board::create(10, 20, 20, true, true, 3),
+            handling: Handling::create(200.0, 33.0, 20.0),
+            difficulty: Difficulty { gravity: 1.0/60.0, lock_delay: 30, lock_delay_resets: 15, spawn_delay: 30, line_clear_delay: 20, stack_invis: false, stack_invis_delay: 240, next_allowed: 3, hold_allowed: true, garbage_entry_delay: 20, garbage_messiness: 0.0 },
+            rotation_system: ROTATION_SYSTEMS["SRS"].clone(),
+            rotation_system_name: String::from("SRS"),
+            next_queue: vec![],
+            hold: None,
+            can_hold: true,
+            g: 0.0,
+            lock_delay: 30,
+            lock_delay_resets: 15,
+            lock_delay_active: false,
+            spawn_delay: 0,
+            line_clear_delay: 0,
+            clearing_lines: vec![],
+            need_to_lock: false,
+            randomizer: Box::new(Bag::create(0)),
+            state: GameloopStates::Init,
+            previous_inputs: Inputs::default(),
+            frame: 0,
+            score: Score::create(ScoringTable::Guideline),
+            stats: Stats::default(),
+            last_placement: Placement::Regular,
+            mode: GameMode::Endless,
+            last_lines_cleared: 0,
+            combo: 0,
+            back_to_back: 0,
+            garbage: Garbage::create(0),
+            attack_table: AttackTable::Guideline,
+            last_attack: 0,
+            attack_total: 0,
+            outgoing_garbage: 0,
+        }
+    }
+}
+
+impl Engine {
+    fn next_to_current(&mut self){
+        if self.next_queue.len() <= self.board.show_next as usize {
+            self.next_queue.append(&mut self.randomizer.populate_next(&self.rotation_system, self.board.width as isize, self.board.height as isize));
+        }
+        self.current_piece = self.next_queue.first().copied();
+        self.next_queue.remove(0);
+    }
+
+    pub fn init(&mut self, rotation_system: &str, randomizer: Box<dyn Randomizer + Sync + Send>, scoring: ScoringTable, mode: GameMode){
+        self.rotation_system = ROTATION_SYSTEMS[rotation_system].clone();
+        self.score = Score::create(scoring);
+        self.mode = mode;
+        self.mode.apply(&mut self.difficulty);
+        self.rotation_system_name = String::from(rotation_system);
+        self.randomizer = randomizer;
+        self.garbage = Garbage::create(self.randomizer.seed());
+        self.top_up_garbage();
+        self.spawn_delay = self.difficulty.spawn_delay;
+        while self.next_queue.len() <= self.board.show_next as usize  {
+            self.next_queue.append(&mut self.randomizer.populate_next(&self.rotation_system, self.board.width as isize, self.board.height as isize));
+        }
+        self.next_to_current();
+        // First piece doesn't go through spawn routine
+        if self.mode.clock_starts_on_spawn() {
+            self.stats.start(self.frame);
+        }
+        self.state = GameloopStates::Falling;
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.state == GameloopStates::GameOver || self.state == GameloopStates::Finished
+    }
+
+    // Advances the game by one frame
+    pub fn step(&mut self, inputs: &Inputs) {
+        if *inputs != Inputs::default() {
+            self.stats.start(self.frame);
+        }
+        self.handle_inputs(inputs);
+        match self.state {
+            GameloopStates::Init => {},
+            GameloopStates::Spawn => self.run_spawn_delay(),
+            GameloopStates::Falling => self.gameloop(),
+            GameloopStates::AfterLocking => self.after_locking_routine(),
+            GameloopStates::LineClear => self.run_line_clear_delay(),
+            GameloopStates::GameOver => {},
+            GameloopStates::Finished => {},
+        }
+        self.previous_inputs = *inputs;
+        self.frame += 1;
+        if !self.is_over() && self.mode.garbage_rises(self.stats.frames(self.frame)) {
+            self.add_garbage(1);
+        }
+        if !self.is_over() && self.mode.is_finished(&self.score, &self.stats, self.frame) {
+            self.finish();
+        }
+    }
+
+    fn finish(&mut self) {
+        let lines = take(&mut self.clearing_lines);
+        self.board.remove_lines(&lines);
+        self.stats.end(self.frame);
+        self.state = GameloopStates::Finished;
+    }
+
+    fn handle_inputs(&mut self, inputs: &Inputs) {
+        let previous = self.previous_inputs;
+        let falling = self.state == GameloopStates::Falling;
+        if inputs.rotate_cw && !previous.rotate_cw && falling {
+            self.rotate_current_piece(1);
+        }
+        if inputs.rotate_ccw && !previous.rotate_ccw && falling {
+            self.rotate_current_piece(-1);
+        }
+        if inputs.rotate_180 && !previous.rotate_180 && falling {
+            self.rotate_current_piece(2);
+        }
+        if inputs.hold && !previous.hold && falling {
+            self.hold_current_piece();
+        }
+        if inputs.left && !previous.left {
+            if falling {
+                self.move_current_piece((-1, 0));
+            }
+            self.handling.movement_key_pressed(true, false);
+        }
+        if inputs.right && !previous.right {
+            if falling {
+                self.move_current_piece((1, 0));
+            }
+            self.handling.movement_key_pressed(false, true);
+        }
+        if !inputs.left && previous.left {
+            self.handling.movement_key_released(true, false);
+        }
+        if !inputs.right && previous.right {
+            self.handling.movement_key_released(false, true);
+        }
+        self.handling.sdf_active = inputs.soft_drop;
+        let direction = self.handling.movement_tick(FRAME_DURATION);
+        if falling {
+            self.move_current_piece((direction, 0));
+        }
+        // drops to the floor without locking, piece stops being rotated last
+        if inputs.sonic_drop && !previous.sonic_drop && falling && self.sonic_drop() {
+            self.current_piece.as_mut().unwrap().last_kick = None;
+        }
+        if inputs.hard_drop && !previous.hard_drop && falling {
+            if let Some(piece) = self.current_piece {
+                // rotation in the air followed by a drop isn't a spin
+                if self.sonic_drop() {
+                    self.current_piece.as_mut().unwrap().last_kick = None;
+                }
+                let cells = piece.position.1 - self.current_piece.as_ref().unwrap().position.1;
+                self.score.hard_drop(cells as u32);
+            }
+            self.lock_current_piece();
+            self.state = GameloopStates::AfterLocking;
+        }
+    }
+
+    fn gameloop(&mut self) {
+        let piece = match self.current_piece {
+            Some(piece) => piece,
+            None => return,
+        };
+        if self.handling.sdf_active {
+            self.g += self.difficulty.gravity * self.handling.sdf;
+        } else {
+            self.g += self.difficulty.gravity;
+        }
+        let mut gravity_tick_happend = false;
+        while self.g >= 1.0 {
+            let moved = self.move_current_piece((0, -1));
+            if moved && self.handling.sdf_active {
+                self.score.soft_drop(1);
+            }
+            self.g -= 1.0;
+            gravity_tick_happend = true;
+        }
+        let previos_lock_delay_active = self.lock_delay_active;
+        self.lock_delay_active =
+            !self.position_is_valid((piece.position.0, piece.position.1 - 1), piece.rotation);
+        if self.lock_delay_active {
+            match self.rotation_system.lock_delay_mode {
+                LockDelayMode::Disabled => {
+                    self.need_to_lock = true;
+                }
+                LockDelayMode::Gravity => {
+                    if gravity_tick_happend && previos_lock_delay_active {
+                        self.need_to_lock = true;
+                    }
+                }
+                LockDelayMode::ResetOnYChange => {
+                    self.lock_delay -= 1;
+                }
+                LockDelayMode::ResetOnMovementLimited => {
+                    self.lock_delay -= 1;
+                }
+                LockDelayMode::ResetOnMovement => {
+                    self.lock_delay -= 1;
+                }
+            }
+        } else {
+            if previos_lock_delay_active {
+                match self.rotation_system.lock_delay_mode {
+                    LockDelayMode::Disabled => {}
+                    LockDelayMode::Gravity => {}
+                    LockDelayMode::ResetOnYChange => {
+                        self.lock_delay = self.difficulty.lock_delay;
+                        if self.lock_delay_resets == 0 {
+                            self.need_to_lock = true;
+                        } else {
+                            self.lock_delay_resets -= 1;
+                        }
+                    }
+                    LockDelayMode::ResetOnMovementLimited => {
+                        self.lock_delay = self.difficulty.lock_delay;
+                    }
+                    LockDelayMode::ResetOnMovement => {
+                        self.lock_delay = self.difficulty.lock_delay;
+                    }
+                }
+            }
+        }
+        if (self.lock_delay < 1 || self.need_to_lock)
+            && !self.position_is_valid((piece.position.0, piece.position.1 - 1), piece.rotation)
+        {
+            self.lock_current_piece();
+            self.state = GameloopStates::AfterLocking;
+        }
+    }
+
+    fn after_locking_routine(&mut self) {
+        let full_lines = self.board.full_lines();
+        let lines = full_lines.len();
+        let lines_before = self.score.lines;
+        self.score.line_clear(lines);
+        self.stats.garbage_cleared += full_lines.iter().filter(|line| is_garbage_row(&self.board.board[**line])).count() as u32;
+        self.stats.lines_cleared(self.frame, lines_before, self.score.lines);
+        self.last_lines_cleared = lines;
+        if lines > 0 {
+            let level_before = self.score.level;
+            self.mode.lines_cleared(lines as u32, &mut self.score, &mut self.difficulty);
+            self.stats.level_up(self.frame, level_before, self.score.level, self.score.score);
+            if self.last_placement.is_spin() {
+                self.score.spin_clears += 1;
+            }
+            self.combo += 1;
+            if lines >= 4 || self.last_placement.is_spin() {
+                self.back_to_back += 1;
+            } else {
+                self.back_to_back = 0;
+            }
+            self.last_attack = self.attack_table.attack(lines, self.last_placement, self.combo - 1, self.back_to_back.saturating_sub(1));
+            self.attack_total += self.last_attack;
+            // attack cancels pending garbage first
+            self.outgoing_garbage += self.garbage.cancel(self.last_attack);
+        } else {
+            self.combo = 0;
+            self.last_attack = 0;
+            let garbage = self.garbage.take_ready(self.frame);
+            self.add_garbage(garbage);
+            if self.is_over() {
+                return;
+            }
+        }
+        if full_lines.is_empty() {
+            self.state = GameloopStates::Spawn;
+        } else {
+            self.clearing_lines = full_lines;
+            self.line_clear_delay = self.difficulty.line_clear_delay;
+            self.state = GameloopStates::LineClear;
+        }
+    }
+
+    // Received garbage goes to the queue and waits for entry delay
+    pub fn receive_garbage(&mut self, lines: u32) {
+        self.garbage.receive(lines, self.frame, self.difficulty.garbage_entry_delay);
+    }
+
+    // Puts garbage under the stack right away. Current piece is pushed up, if it overlaps with the stack
+    pub fn add_garbage(&mut self, lines: u32) {
+        if lines == 0 {
+            return;
+        }
+        let rows = self.garbage.rows(lines, self.board.width, self.difficulty.garbage_messiness, self.frame);
+        let fits = self.board.insert_rows(rows);
+        for line in self.clearing_lines.iter_mut() {
+            *line += lines as usize;
+        }
+        self.clearing_lines.retain(|line| *line < self.board.board.len());
+        if let Some(mut piece) = self.current_piece {
+            while !self.position_is_valid(piece.position, piece.rotation) && piece.position.1 < self.board.board.len() as isize {
+                piece.position.1 += 1;
+            }
+            self.current_piece = Some(piece);
+        }
+        let piece_fits = match self.current_piece {
+            Some(piece) => self.position_is_valid(piece.position, piece.rotation),
+            None => true,
+        };
+        if !fits || !piece_fits {
+            self.stats.end(self.frame);
+            self.state = GameloopStates::GameOver;
+        }
+    }
+
+    // Modes like dig keep some garbage on the board
+    fn top_up_garbage(&mut self) {
+        let rows_on_board = self.board.board.iter().filter(|row| is_garbage_row(row)).count() as u32;
+        let rows = self.mode.garbage_top_up(rows_on_board, self.garbage.added);
+        self.add_garbage(rows);
+    }
+
+    fn run_line_clear_delay(&mut self) {
+        if self.line_clear_delay > 0 {
+            self.line_clear_delay -= 1;
+        } else {
+            let lines = take(&mut self.clearing_lines);
+            self.board.remove_lines(&lines);
+            self.top_up_garbage();
+            if self.is_over() {
+                return;
+            }
+            self.state = GameloopStates::Spawn;
+        }
+    }
+
+    fn run_spawn_delay(&mut self) {
+        if self.spawn_delay > 0 {
+            self.spawn_delay -= 1;
+        } else {
+            self.spawn_delay = self.difficulty.spawn_delay;
+            self.spawn_routine();
+        }
+    }
+
+    fn spawn_routine(&mut self) {
+        self.lock_delay = self.difficulty.lock_delay;
+        self.lock_delay_resets = self.difficulty.lock_delay_resets;
+        self.lock_delay_active = false;
+        if self.spawn_sequence() {
+            if self.mode.clock_starts_on_spawn() {
+                self.stats.start(self.frame);
+            }
+            let level_before = self.score.level;
+            self.mode.piece_spawned(&mut self.score, &mut self.difficulty);
+            self.stats.level_up(self.frame, level_before, self.score.level, self.score.score);
+            self.state = GameloopStates::Falling;
+        } else {
+            self.stats.end(self.frame);
+            self.state = GameloopStates::GameOver;
+        }
+    }
+
+    pub fn spawn_sequence(&mut self) -> bool {
+        self.next_to_current();
+        if !self.position_is_valid(self.current_piece.as_ref().unwrap().position, self.current_piece.as_ref().unwrap().rotation){
+            return false;
+        }
+        self.can_hold = true;
+        if self.difficulty.gravity >= 20.0 { self.current_piece.as_mut().unwrap().position.1 = self.lowest_point_under_current_piece() }
+        true
+    }
+
+    pub fn hold_current_piece(&mut self) -> bool {
+        if  !self.difficulty.hold_allowed || !self.can_hold {
+            return false;
+        }
+        self.current_piece.as_mut().unwrap().rotation = 0;
+        self.current_piece.as_mut().unwrap().last_kick = None;
+        self.current_piece.as_mut().unwrap().position = (3+self.rotation_system.spawn_offsets[self.current_piece.as_ref().unwrap().id].0, 20+self.rotation_system.spawn_offsets[self.current_piece.as_ref().unwrap().id].1);
+        match self.hold {
+            Some(_) => {
+                swap(&mut self.current_piece, &mut self.hold);
+            }
+            None => {
+                self.hold = self.current_piece;
+                self.next_to_current();
+            },
+        }
+        self.can_hold = false;
+        true
+    }
+
+    pub fn lock_current_piece(&mut self) -> bool {
+        if self.position_is_valid((self.current_piece.as_ref().unwrap().position.0, self.current_piece.as_ref().unwrap().position.1-1), self.current_piece.as_ref().unwrap().rotation) {
+            return false;
+        }
+        self.last_placement = classify_placement(&self.board, &self.rotation_system, self.current_piece.as_ref().unwrap());
+        let minos_to_write = &self.rotation_system.pieces[self.current_piece.as_ref().unwrap().id][self.current_piece.as_ref().unwrap().rotation];
+        for mino in minos_to_write{
+            let x = (self.current_piece.as_ref().unwrap().position.0 + mino.0 as isize) as usize;
+            let y = (self.current_piece.as_ref().unwrap().position.1 + mino.1 as isize) as usize;
+            self.board.board[y][x] = Some(Mino{ color: self.rotation_system.colours[self.current_piece.as_ref().unwrap().id], locked_at: self.frame });
+        }
+        self.current_piece = None;
+        self.stats.pieces += 1;
+        self.need_to_lock = false;
+        true
+    }
+
+    // From 1.0 (fully visible) to 0.0. With stack_invis locked minos fade out after
+    // stack_invis_delay frames, whole stack is revealed when game is over
+    pub fn mino_alpha(&self, mino: &Mino) -> f32 {
+        if !self.difficulty.stack_invis || self.is_over() {
+            return 1.0;
+        }
+        let age = self.frame.saturating_sub(mino.locked_at);
+        let delay = self.difficulty.stack_invis_delay as u64;
+        if delay == 0 {
+            // fully invisible, only the lock flash shows where piece went
+            return if age < LOCK_FLASH_FRAMES { 1.0 } else { 0.0 };
+        }
+        if age < delay {
+            1.0
+        } else if age < delay + STACK_FADE_FRAMES {
+            1.0 - (age - delay) as f32 / STACK_FADE_FRAMES as f32
+        } else {
+            0.0
+        }
+    }
+
+    // Outline flash on lock, only stacks that fade need it
+    pub fn mino_just_locked(&self, mino: &Mino) -> bool {
+        self.difficulty.stack_invis && self.frame.saturating_sub(mino.locked_at) < LOCK_FLASH_FRAMES
+    }
+
+    pub fn sonic_drop(&mut self) -> bool {
+        if self.current_piece.is_none(){
+            return false;
+        }
+        if !self.position_is_valid((self.current_piece.as_ref().unwrap().position.0, self.current_piece.as_ref().unwrap().position.1-1), self.current_piece.as_ref().unwrap().rotation) {
+            return false;
+        }
+        self.current_piece.as_mut().unwrap().position.1 = self.lowest_point_under_current_piece();
+        true
+    }
+
+    pub fn lowest_point_under_current_piece(&self) -> isize{
+        let mut y = self.current_piece.as_ref().unwrap().position.1;
+        while self.position_is_valid((self.current_piece.as_ref().unwrap().position.0, y-1), self.current_piece.as_ref().unwrap().rotation){
+            y -= 1
+        }     
+        y
+    }
+
+    fn reset_lock_delay(&mut self){
+        match self.rotation_system.lock_delay_mode {
+            LockDelayMode::Disabled => {},
+            LockDelayMode::Gravity => {
+                if self.position_is_valid((self.current_piece.as_ref().unwrap().position.0, self.current_piece.as_ref().unwrap().position.1-1), self.current_piece.as_ref().unwrap().rotation) {
+                    self.lock_delay_active = false;
+                }
+            },
+            LockDelayMode::ResetOnYChange => {},
+            LockDelayMode::ResetOnMovementLimited => {
+                if !self.position_is_valid((self.current_piece.as_ref().unwrap().position.0, self.current_piece.as_ref().unwrap().position.1-1), self.current_piece.as_ref().unwrap().rotation){
+                    self.lock_delay = self.difficulty.lock_delay;
+                    if self.lock_delay_resets == 0{
+                        self.need_to_lock = true;
+                    }else{
+                        self.lock_delay_resets -= 1;
+                        self.lock_delay_active = false;
+                    }
+                }
+            },
+            LockDelayMode::ResetOnMovement => {
+                if !self.position_is_valid((self.current_piece.as_ref().unwrap().position.0, self.current_piece.as_ref().unwrap().position.1-1), self.current_piece.as_ref().unwrap().rotation){
+                    self.lock_delay = self.difficulty.lock_delay;
+                    self.lock_delay_active = false;
+                }
+            },
+        }
+    }
+
+    pub fn rotate_current_piece(&mut self, rotation: i8) -> bool {
+        if self.current_piece.is_none(){
+            return false;
+        }
+        let future_rotation = (self.current_piece.as_ref().unwrap().rotation as i8 + rotation) as usize % self.rotation_system.pieces[self.current_piece.as_ref().unwrap().id].len();
+        let id_for_kicks: usize = match rotation {
+            1 => KICKS_CW,
+            2 => KICKS_180,
+            _ => KICKS_CCW,
+        };
+        if id_for_kicks == KICKS_180 && !self.rotation_system.allow_180 {
+            return false;
+        }
+        self.reset_lock_delay();
+        for (kick, test) in self.rotation_system.kicks[self.current_piece.as_ref().unwrap().id][self.current_piece.as_ref().unwrap().rotation][id_for_kicks].iter().enumerate(){
+            let future_position = (self.current_piece.as_ref().unwrap().position.0 + test.0 as isize, self.current_piece.as_ref().unwrap().position.1 + test.1 as isize);
+            if self.position_is_valid(future_position, future_rotation) {
+                self.current_piece.as_mut().unwrap().rotation = future_rotation;
+                self.current_piece.as_mut().unwrap().position = future_position;
+                self.current_piece.as_mut().unwrap().last_kick = Some((id_for_kicks, kick));
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn move_current_piece(&mut self, shift: (i8, i8)) -> bool {
+        if (shift.0 == 0 && shift.1 == 0) || self.current_piece.is_none(){
+            return true;
+        }
+        let future_position = (
+            self.current_piece.as_ref().unwrap().position.0 + shift.0 as isize, // future X
+            self.current_piece.as_ref().unwrap().position.1 + shift.1 as isize  // future Y
+        );
+        if self.position_is_valid(future_position, self.current_piece.as_ref().unwrap().rotation) {
+            if shift.0 != 0 {self.reset_lock_delay();}
+            self.current_piece.as_mut().unwrap().position = future_position;
+            self.current_piece.as_mut().unwrap().last_kick = None;
+            true
+        }else {
+            false
+        }
+    }
+
+    pub fn position_is_valid(&self, future_position: (isize, isize), future_rotation: usize) -> bool {
+        for mino in &self.rotation_system.pieces[self.current_piece.as_ref().unwrap().id][future_rotation]{
+            match self.board.board.get((future_position.1 + mino.1 as isize) as usize) {
+                Some(line) => match line.get((future_position.0 + mino.0 as isize) as usize) {
+                    Some(cell) => match cell {
+                        Some(_) => return false,
+                        None => continue,
+                    },
+                    None => return false,
+                },
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::garbage::GARBAGE_COLOR;
+
+    const MINO: Option<Mino> = Some(Mino { color: Color::RED, locked_at: 0 });
+
+    fn engine() -> Engine {
+        engine_with("SRS")
+    }
+
+    fn engine_with(rotation_system: &str) -> Engine {
+        let mut engine = Engine::default();
+        engine.init(rotation_system, Box::new(Bag::create(1)), ScoringTable::Guideline, GameMode::Endless);
+        engine
+    }
+
+    fn engine_in(mode: GameMode) -> Engine {
+        let mut engine = Engine::default();
+        engine.init("SRS", Box::new(Bag::create(1)), ScoringTable::Guideline, mode);
+        engine
+    }
+
+    #[test]
+    fn inserted_rows_push_stack_up() {
+        let mut board = Board::create(10, 20, 20, true, true, 3);
+        board.board[0][0] = MINO;
+        let rows = vec![vec![Some(Mino { color: GARBAGE_COLOR, locked_at: 0 }); 10]; 2];
+        assert!(board.insert_rows(rows));
+        assert_eq!(board.board.len(), 40);
+        assert!(board.board[0].iter().all(|cell| cell.is_some()));
+        assert!(board.board[1].iter().all(|cell| cell.is_some()));
+        assert!(board.board[2][0].is_some() && board.board[2][1].is_none());
+    }
+
+    #[test]
+    fn rows_pushed_out_of_board_are_reported() {
+        let mut board = Board::create(10, 20, 20, true, true, 3);
+        board.board[39][5] = MINO;
+        assert!(!board.insert_rows(vec![vec![None; 10]]));
+    }
+
+    #[test]
+    fn garbage_lifts_current_piece() {
+        let mut engine = engine();
+        engine.sonic_drop();
+        let before = engine.current_piece.unwrap().position.1;
+        engine.add_garbage(3);
+        assert_eq!(engine.state, GameloopStates::Falling);
+        assert_eq!(engine.garbage.added, 3);
+        assert!(engine.current_piece.unwrap().position.1 >= before + 3);
+        // one hole in every garbage row
+        assert_eq!(engine.board.board[0].iter().filter(|cell| cell.is_none()).count(), 1);
+    }
+
+    #[test]
+    fn too_much_garbage_tops_out() {
+        let mut engine = engine();
+        let rows = engine.board.board.len() as u32;
+        engine.add_garbage(rows);
+        assert_eq!(engine.state, GameloopStates::GameOver);
+        assert!(engine.stats.ended_at.is_some());
+    }
+
+    #[test]
+    fn rotate_180_on_srs() {
+        let mut engine = engine();
+        let before = engine.current_piece.unwrap();
+        let rotations = engine.rotation_system.pieces[before.id].len();
+        engine.step(&Inputs { rotate_180: true, ..Default::default() });
+        let after = engine.current_piece.unwrap();
+        assert_eq!(after.rotation, (before.rotation + 2) % rotations);
+        assert_eq!(after.last_kick, Some((KICKS_180, 0)));
+    }
+
+    #[test]
+    fn rotate_180_is_refused_without_180_kicks() {
+        for rotation_system in ["ARS", "NRS"] {
+            let mut engine = engine_with(rotation_system);
+            let before = engine.current_piece.unwrap();
+            assert!(!engine.rotate_current_piece(2), "{}", rotation_system);
+            assert_eq!(engine.current_piece.unwrap().rotation, before.rotation, "{}", rotation_system);
+            assert_eq!(engine.current_piece.unwrap().last_kick, None, "{}", rotation_system);
+        }
+    }
+
+    #[test]
+    fn rotate_180_uses_180_kick_table() {
+        let mut engine = engine();
+        let piece = engine.current_piece.unwrap();
+        let kicks = &mut engine.rotation_system.kicks[piece.id][piece.rotation];
+        kicks[KICKS_180] = vec![(0, -100), (1, 0)];
+        kicks[KICKS_CW] = vec![(0, -100)];
+        assert!(!engine.rotate_current_piece(1));
+        assert!(engine.rotate_current_piece(2));
+        let rotated = engine.current_piece.unwrap();
+        assert_eq!(rotated.position, (piece.position.0 + 1, piece.position.1));
+        assert_eq!(rotated.last_kick, Some((KICKS_180, 1)));
+    }
+
+    #[test]
+    fn hard_drop_after_rotation_in_air_is_not_spin() {
+        let mut engine = engine();
+        engine.rotate_current_piece(1);
+        assert!(engine.current_piece.unwrap().last_kick.is_some());
+        engine.step(&Inputs { hard_drop: true, ..Default::default() });
+        assert_eq!(engine.last_placement, Placement::Regular);
+    }
+
+    #[test]
+    fn ultra_clock_runs_from_first_piece() {
+        let mut engine = engine_in(GameMode::Ultra { seconds: 120 });
+        assert_eq!(engine.stats.started_at, Some(0));
+        for _ in 0..10 {
+            engine.step(&Inputs::default());
+        }
+        assert_eq!(engine.mode.frames_left(engine.stats.frames(engine.frame)), Some(120 * FPS - 10));
+    }
+
+    #[test]
+    fn survival_garbage_rises_while_idle() {
+        let mut engine = engine_in(GameMode::Survival { seconds: 10 });
+        assert_eq!(engine.stats.started_at, Some(0));
+        for _ in 0..10 * FPS - 1 {
+            engine.step(&Inputs::default());
+        }
+        assert_eq!(engine.garbage.added, 0);
+        engine.step(&Inputs::default());
+        assert_eq!(engine.garbage.added, 1);
+    }
+}
@@ -0,0 +1,81 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::game::{Color, Mino};
+
+pub const GARBAGE_COLOR: Color = Color { red: 0.5, green: 0.5, blue: 0.5, alpha: 1.0 };
+
+#[derive(Clone, Copy, Debug)]
+pub struct PendingGarbage {
+    pub lines: u32,
+    pub ready_at: u64 // frame, when it can enter the board
+}
+
+// Incoming garbage waits in the queue for entry delay, line clears cancel it before that
+pub struct Garbage {
+    pub queue: Vec<PendingGarbage>, // oldest first
+    pub added: u32, // rows put on the board since start
+    hole: usize,
+    rng: StdRng
+}
+
+impl Garbage {
+    pub fn create(seed: u64) -> Garbage {
+        let mut rng = StdRng::seed_from_u64(seed);
+        Garbage { queue: vec![], added: 0, hole: rng.gen(), rng }
+    }
+
+    pub fn receive(&mut self, lines: u32, frame: u64, entry_delay: u8) {
+        if lines > 0 {
+            self.queue.push(PendingGarbage { lines, ready_at: frame + entry_delay as u64 });
+        }
+    }
+
+    // Removes up to that many lines from the queue, oldest first. Returns what's left after cancelling
+    pub fn cancel(&mut self, mut lines: u32) -> u32 {
+        while lines > 0 && !self.queue.is_empty() {
+            let cancelled = lines.min(self.queue[0].lines);
+            self.queue[0].lines -= cancelled;
+            lines -= cancelled;
+            if self.queue[0].lines == 0 {
+                self.queue.remove(0);
+            }
+        }
+        lines
+    }
+
+    pub fn pending(&self) -> u32 {
+        self.queue.iter().map(|garbage| garbage.lines).sum()
+    }
+
+    // Takes lines, that waited long enough
+    pub fn take_ready(&mut self, frame: u64) -> u32 {
+        let mut lines = 0;
+        while !self.queue.is_empty() && self.queue[0].ready_at <= frame {
+            lines += self.queue.remove(0).lines;
+        }
+        lines
+    }
+
+    // Rows from bottom to top. Messiness is a chance of hole moving to another column
+    // on every row: 0.0 gives clean well, 1.0 gives random hole on every row
+    pub fn rows(&mut self, lines: u32, width: u8, messiness: f32, frame: u64) -> Vec<Vec<Option<Mino>>> {
+        let mut rows = vec![];
+        for _ in 0..lines {
+            if self.rng.gen::<f32>() < messiness {
+                self.hole = self.rng.gen();
+            }
+            let hole = self.hole % width as usize;
+            let mut row = vec![Some(Mino { color: GARBAGE_COLOR, locked_at: frame }); width as usize];
+            row[hole] = None;
+            rows.push(row);
+        }
+        self.added += lines;
+        rows
+    }
+}
+
+// Row has at least one garbage mino, even if it was covered by pieces
+pub fn is_garbage_row(row: &[Option<Mino>]) -> bool {
+    row.iter().any(|cell| matches!(cell, Some(mino) if mino.color == GARBAGE_COLOR))
+}
@@ -0,0 +1,15 @@
+// Rules of the game without any Bevy stuff: board, pieces, handling, rotation systems
+// and randomizers. Call Engine::step once per frame with inputs of that frame
+pub mod game;
+pub mod randomizers;
+pub mod rotation_systems;
+pub mod replay;
+pub mod scoring;
+pub mod spins;
+pub mod modes;
+pub mod stats;
+pub mod master;
+pub mod garbage;
+pub mod attack;
+pub mod settings;
+pub mod ron_file;
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{game::{Board, Difficulty, FPS}, scoring::Score, stats::Stats};
+
+pub const MAX_LEVEL: u32 = 999;
+pub const SECTION_LEVELS: u32 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MasterVersion {
+    TGM1, // same delays for the whole game
+    TGM2 // delays shrink every section after 500
+}
+
+// (level, gravity in 1/256 G), TGM2 Master uses the same curve as TGM1
+const GRAVITY: [(u32, u32); 30] = [
+    (0, 4), (30, 6), (35, 8), (40, 10), (50, 12), (60, 16), (70, 32), (80, 48), (90, 64), (100, 80),
+    (120, 96), (140, 112), (160, 128), (170, 144), (200, 4), (220, 32), (230, 64), (233, 96), (236, 128), (239, 160),
+    (243, 192), (247, 224), (251, 256), (300, 512), (330, 768), (360, 1024), (400, 1280), (420, 1024), (450, 768), (500, 5120)
+];
+
+// (level, spawn delay, lock delay, line clear delay) in frames
+const TGM1_DELAYS: [(u32, u8, u8, u8); 1] = [(0, 30, 30, 41)];
+const TGM2_DELAYS: [(u32, u8, u8, u8); 6] = [
+    (0, 25, 30, 40),
+    (500, 25, 30, 25),
+    (600, 25, 30, 16),
+    (700, 16, 30, 12),
+    (800, 12, 30, 6),
+    (900, 12, 17, 6),
+];
+
+// (score, grade)
+const GRADES: [(u64, &str); 18] = [
+    (0, "9"), (400, "8"), (800, "7"), (1400, "6"), (2000, "5"), (3500, "4"), (5500, "3"), (8000, "2"), (12000, "1"),
+    (16000, "S1"), (22000, "S2"), (30000, "S3"), (40000, "S4"), (52000, "S5"), (66000, "S6"), (82000, "S7"), (100000, "S8"), (120000, "S9")
+];
+
+// Rows of secret grade staircase, that are needed for GM
+const SECRET_GM_ROWS: usize = 19;
+
+// GM requirements: (level, score, time in seconds)
+const GM_CHECKPOINTS: [(u32, u64, u64); 3] = [(300, 12000, 255), (500, 40000, 450), (MAX_LEVEL, 126000, 810)];
+
+impl MasterVersion {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MasterVersion::TGM1 => "TGM1",
+            MasterVersion::TGM2 => "TGM2",
+        }
+    }
+
+    // Sets speed and delays of that level
+    pub fn apply(&self, level: u32, difficulty: &mut Difficulty) {
+        let gravity = GRAVITY.iter().rev().find(|(from, _)| *from <= level).unwrap().1;
+        difficulty.gravity = gravity as f32 / 256.0;
+        let delays: &[(u32, u8, u8, u8)] = match self {
+            MasterVersion::TGM1 => &TGM1_DELAYS,
+            MasterVersion::TGM2 => &TGM2_DELAYS,
+        };
+        let (_, spawn_delay, lock_delay, line_clear_delay) = *delays.iter().rev().find(|(from, ..)| *from <= level).unwrap();
+        difficulty.spawn_delay = spawn_delay;
+        difficulty.lock_delay = lock_delay;
+        difficulty.line_clear_delay = line_clear_delay;
+    }
+}
+
+// Pieces can't move level past the end of section (x99) or to the last level, only line clears can
+pub fn level_after_piece(level: u32) -> u32 {
+    if level % SECTION_LEVELS == SECTION_LEVELS - 1 || level >= MAX_LEVEL - 1 {
+        level
+    } else {
+        level + 1
+    }
+}
+
+pub fn level_after_lines(level: u32, lines: u32) -> u32 {
+    (level + lines).min(MAX_LEVEL)
+}
+
+// Level where current section ends
+pub fn section_goal(level: u32) -> u32 {
+    ((level / SECTION_LEVELS + 1) * SECTION_LEVELS).min(MAX_LEVEL)
+}
+
+pub fn grade(score: &Score, stats: &Stats, frame: u64) -> &'static str {
+    let is_gm = GM_CHECKPOINTS.iter().all(|(level, min_score, seconds)| {
+        let reached = if *level == MAX_LEVEL {
+            if score.level >= MAX_LEVEL { Some((stats.frames(frame), score.score)) } else { None }
+        } else {
+            stats.sections.get((level / SECTION_LEVELS) as usize - 1).copied()
+        };
+        match reached {
+            Some((frames, score)) => score >= *min_score && frames <= seconds * FPS,
+            None => false,
+        }
+    });
+    if is_gm {
+        return "GM";
+    }
+    GRADES.iter().rev().find(|(min_score, _)| *min_score <= score.score).unwrap().1
+}
+
+// Secret grade: from the bottom, every row has exactly one hole, holes go diagonally
+// to the right wall and back, making a "<" shape. Grade depends on how many rows follow it
+pub fn secret_grade(board: &Board) -> Option<&'static str> {
+    let width = board.width as usize;
+    let mut rows = 0;
+    for (y, line) in board.board.iter().enumerate().take(SECRET_GM_ROWS.min(2 * width - 1)) {
+        let hole = if y < width { y } else { 2 * (width - 1) - y };
+        let follows = line.iter().enumerate().all(|(x, cell)| cell.is_none() == (x == hole));
+        if !follows {
+            break;
+        }
+        rows += 1;
+    }
+    match rows {
+        0 => None,
+        SECRET_GM_ROWS => Some("GM"),
+        _ => Some(GRADES[rows - 1].1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Color, Engine, Mino};
+
+    #[test]
+    fn pieces_stop_at_section_end() {
+        assert_eq!(level_after_piece(0), 1);
+        assert_eq!(level_after_piece(98), 99);
+        assert_eq!(level_after_piece(99), 99);
+        assert_eq!(level_after_piece(199), 199);
+        assert_eq!(level_after_piece(997), 998);
+        assert_eq!(level_after_piece(998), 998);
+    }
+
+    #[test]
+    fn lines_pass_section_end_up_to_max_level() {
+        assert_eq!(level_after_lines(99, 2), 101);
+        assert_eq!(level_after_lines(998, 1), MAX_LEVEL);
+        assert_eq!(level_after_lines(997, 4), MAX_LEVEL);
+        assert_eq!(section_goal(0), 100);
+        assert_eq!(section_goal(950), MAX_LEVEL);
+    }
+
+    #[test]
+    fn speed_and_delays_follow_level() {
+        let mut difficulty = Engine::default().difficulty;
+        MasterVersion::TGM1.apply(0, &mut difficulty);
+        assert_eq!(difficulty.gravity, 4.0 / 256.0);
+        assert_eq!((difficulty.spawn_delay, difficulty.lock_delay, difficulty.line_clear_delay), (30, 30, 41));
+        MasterVersion::TGM1.apply(232, &mut difficulty);
+        assert_eq!(difficulty.gravity, 64.0 / 256.0);
+        MasterVersion::TGM1.apply(900, &mut difficulty);
+        assert_eq!(difficulty.gravity, 20.0);
+        assert_eq!((difficulty.spawn_delay, difficulty.lock_delay, difficulty.line_clear_delay), (30, 30, 41));
+        MasterVersion::TGM2.apply(500, &mut difficulty);
+        assert_eq!((difficulty.spawn_delay, difficulty.lock_delay, difficulty.line_clear_delay), (25, 30, 25));
+        MasterVersion::TGM2.apply(999, &mut difficulty);
+        assert_eq!((difficulty.spawn_delay, difficulty.lock_delay, difficulty.line_clear_delay), (12, 17, 6));
+    }
+
+    // Finished game, that passed every section in time with enough score for GM
+    fn finished_game() -> (Score, Stats, u64) {
+        let score = Score { level: MAX_LEVEL, score: 130000, ..Default::default() };
+        let sections = (1..10).map(|section| (section * 80 * FPS, section * 10000)).collect();
+        let stats = Stats { started_at: Some(0), ended_at: Some(800 * FPS), sections, ..Default::default() };
+        (score, stats, 800 * FPS)
+    }
+
+    #[test]
+    fn gm_needs_every_checkpoint() {
+        let (score, stats, frame) = finished_game();
+        assert_eq!(grade(&score, &stats, frame), "GM");
+
+        let mut slow = stats.clone();
+        slow.sections[4].0 = 451 * FPS;
+        assert_eq!(grade(&score, &slow, frame), "S9");
+
+        let mut low_score = stats.clone();
+        low_score.sections[2].1 = 11999;
+        assert_eq!(grade(&score, &low_score, frame), "S9");
+
+        let unfinished = Score { level: 998, ..score.clone() };
+        assert_eq!(grade(&unfinished, &stats, frame), "S9");
+    }
+
+    #[test]
+    fn grade_follows_score() {
+        let (_, stats, frame) = finished_game();
+        let grade_of = |points| grade(&Score { score: points, ..Default::default() }, &stats, frame);
+        assert_eq!(grade_of(0), "9");
+        assert_eq!(grade_of(1399), "7");
+        assert_eq!(grade_of(12000), "1");
+        assert_eq!(grade_of(16000), "S1");
+    }
+
+    // Fills rows of the "<" staircase from the bottom
+    fn staircase(rows: usize) -> Board {
+        let mut board = Board::create(10, 20, 20, true, true, 3);
+        let width = board.width as usize;
+        for y in 0..rows {
+            let hole = if y < width { y } else { 2 * (width - 1) - y };
+            for x in 0..width {
+                if x != hole {
+                    board.board[y][x] = Some(Mino { color: Color::RED, locked_at: 0 });
+                }
+            }
+        }
+        board
+    }
+
+    #[test]
+    fn empty_board_has_no_secret_grade() {
+        assert_eq!(secret_grade(&staircase(0)), None);
+    }
+
+    #[test]
+    fn grade_grows_with_staircase() {
+        assert_eq!(secret_grade(&staircase(1)), Some("9"));
+        assert_eq!(secret_grade(&staircase(5)), Some("5"));
+        assert_eq!(secret_grade(&staircase(SECRET_GM_ROWS - 1)), Some("S9"));
+        assert_eq!(secret_grade(&staircase(SECRET_GM_ROWS)), Some("GM"));
+    }
+
+    #[test]
+    fn broken_row_stops_staircase() {
+        let mut board = staircase(SECRET_GM_ROWS);
+        board.board[3][9] = None; // second hole in the fourth row
+        assert_eq!(secret_grade(&board), Some("7"));
+    }
+}
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{game::{Difficulty, FPS}, master::{self, MasterVersion, MAX_LEVEL}, scoring::{Score, ScoringTable}, stats::Stats};
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Endless,
+    Fading, // stack fades out few seconds after lock
+    Invisible, // stack disappears right after lock
+    Sprint { lines: u32 }, // clear that many lines as fast as possible
+    Ultra { seconds: u32 }, // score as much as possible before time runs out
+    Marathon { lines_per_level: u32, levels: u32 }, // speed goes up every level
+    Master { version: MasterVersion }, // reach level 999 with best grade, level goes up with pieces and lines
+    Dig { lines: u32, visible: u32 }, // clear that many garbage lines, board is kept filled with up to visible rows of it
+    Survival { seconds: u32 } // garbage rises from the bottom, first time after that many seconds, then faster and faster
+}
+
+const SURVIVAL_MIN_INTERVAL: u64 = FPS; // frames between garbage rises never go below that
+
+pub struct LevelRules {
+    pub gravity: f32, // G
+    pub lock_delay: u8, // frames
+    pub spawn_delay: u8, // frames
+    pub line_clear_delay: u8 // frames
+}
+
+// Gravity follows guideline curve (0.8 - (level - 1) * 0.007)^(level - 1) seconds per row, capped at 20G.
+// Levels above the table use its last row
+pub const MARATHON_LEVELS: [LevelRules; 20] = [
+    LevelRules { gravity: 0.01667, lock_delay: 30, spawn_delay: 30, line_clear_delay: 20 }, // 1
+    LevelRules { gravity: 0.02102, lock_delay: 30, spawn_delay: 30, line_clear_delay: 20 },
+    LevelRules { gravity: 0.02698, lock_delay: 30, spawn_delay: 28, line_clear_delay: 20 },
+    LevelRules { gravity: 0.03526, lock_delay: 30, spawn_delay: 28, line_clear_delay: 18 },
+    LevelRules { gravity: 0.04693, lock_delay: 30, spawn_delay: 26, line_clear_delay: 18 }, // 5
+    LevelRules { gravity: 0.06361, lock_delay: 30, spawn_delay: 26, line_clear_delay: 16 },
+    LevelRules { gravity: 0.0879, lock_delay: 30, spawn_delay: 24, line_clear_delay: 16 },
+    LevelRules { gravity: 0.1236, lock_delay: 30, spawn_delay: 24, line_clear_delay: 14 },
+    LevelRules { gravity: 0.1775, lock_delay: 30, spawn_delay: 22, line_clear_delay: 14 },
+    LevelRules { gravity: 0.2598, lock_delay: 30, spawn_delay: 22, line_clear_delay: 12 }, // 10
+    LevelRules { gravity: 0.388, lock_delay: 30, spawn_delay: 20, line_clear_delay: 12 },
+    LevelRules { gravity: 0.59, lock_delay: 30, spawn_delay: 20, line_clear_delay: 10 },
+    LevelRules { gravity: 0.92, lock_delay: 30, spawn_delay: 18, line_clear_delay: 10 },
+    LevelRules { gravity: 1.46, lock_delay: 30, spawn_delay: 18, line_clear_delay: 8 },
+    LevelRules { gravity: 2.36, lock_delay: 30, spawn_delay: 16, line_clear_delay: 8 }, // 15
+    LevelRules { gravity: 3.91, lock_delay: 28, spawn_delay: 16, line_clear_delay: 6 },
+    LevelRules { gravity: 6.61, lock_delay: 26, spawn_delay: 14, line_clear_delay: 6 },
+    LevelRules { gravity: 11.43, lock_delay: 24, spawn_delay: 14, line_clear_delay: 4 },
+    LevelRules { gravity: 20.0, lock_delay: 22, spawn_delay: 12, line_clear_delay: 4 },
+    LevelRules { gravity: 20.0, lock_delay: 20, spawn_delay: 12, line_clear_delay: 4 }, // 20
+];
+
+impl LevelRules {
+    pub fn apply(&self, difficulty: &mut Difficulty) {
+        difficulty.gravity = self.gravity;
+        difficulty.lock_delay = self.lock_delay;
+        difficulty.spawn_delay = self.spawn_delay;
+        difficulty.line_clear_delay = self.line_clear_delay;
+    }
+}
+
+pub const GAME_MODES: [GameMode; 15] = [
+    GameMode::Endless,
+    GameMode::Fading,
+    GameMode::Invisible,
+    GameMode::Sprint { lines: 20 },
+    GameMode::Sprint { lines: 40 },
+    GameMode::Sprint { lines: 100 },
+    GameMode::Ultra { seconds: 120 },
+    GameMode::Ultra { seconds: 180 },
+    GameMode::Marathon { lines_per_level: 10, levels: 15 },
+    GameMode::Marathon { lines_per_level: 10, levels: 20 },
+    GameMode::Master { version: MasterVersion::TGM1 },
+    GameMode::Master { version: MasterVersion::TGM2 },
+    GameMode::Dig { lines: 10, visible: 10 },
+    GameMode::Dig { lines: 100, visible: 10 },
+    GameMode::Survival { seconds: 10 }
+];
+
+impl GameMode {
+    pub fn name(&self) -> String {
+        match self {
+            GameMode::Endless => String::from("Endless"),
+            GameMode::Fading => String::from("Fading"),
+            GameMode::Invisible => String::from("Invisible"),
+            GameMode::Sprint { lines } => format!("Sprint {}L", lines),
+            GameMode::Ultra { seconds } => format!("Ultra {}:{:02}", seconds / 60, seconds % 60),
+            GameMode::Marathon { lines_per_level, levels } => format!("Marathon {}L", lines_per_level * levels),
+            GameMode::Master { version } => format!("Master ({})", version.name()),
+            GameMode::Dig { lines, .. } => format!("Dig {}L", lines),
+            GameMode::Survival { .. } => String::from("Survival"),
+        }
+    }
+
+    // Rotation system and randomizer, that mode can't be played without
+    pub fn required_rules(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            GameMode::Master { .. } => Some(("ARS", "TGM")),
+            _ => None,
+        }
+    }
+
+    // Preferred table is used unless mode has its own
+    pub fn scoring_table(&self, preferred: ScoringTable) -> ScoringTable {
+        match self {
+            GameMode::Master { .. } => ScoringTable::Master,
+            _ => preferred,
+        }
+    }
+
+    // Changes rules to the ones of that mode, called once when game starts
+    pub fn apply(&self, difficulty: &mut Difficulty) {
+        match self {
+            GameMode::Endless => {},
+            GameMode::Fading => {
+                difficulty.stack_invis = true;
+                difficulty.stack_invis_delay = 240;
+            },
+            GameMode::Invisible => {
+                difficulty.stack_invis = true;
+                difficulty.stack_invis_delay = 0;
+            },
+            GameMode::Sprint { .. } => {},
+            GameMode::Ultra { .. } => {},
+            GameMode::Marathon { .. } => MARATHON_LEVELS[0].apply(difficulty),
+            GameMode::Master { version } => {
+                version.apply(0, difficulty);
+                difficulty.lock_delay_resets = u8::MAX; // any step down resets lock delay
+            },
+            GameMode::Dig { .. } => difficulty.garbage_messiness = 1.0,
+            GameMode::Survival { .. } => difficulty.garbage_messiness = 0.3,
+        }
+    }
+
+    // Called every time new piece spawns
+    pub fn piece_spawned(&self, score: &mut Score, difficulty: &mut Difficulty) {
+        if let GameMode::Master { version } = self {
+            score.level = master::level_after_piece(score.level);
+            version.apply(score.level, difficulty);
+        }
+    }
+
+    // Called after every line clear, modes with levels raise them here
+    pub fn lines_cleared(&self, lines: u32, score: &mut Score, difficulty: &mut Difficulty) {
+        match self {
+            GameMode::Marathon { lines_per_level, levels } => {
+                let level = (score.lines / lines_per_level).min(levels - 1);
+                score.level = score.table.starting_level() + level;
+                MARATHON_LEVELS[(level as usize).min(MARATHON_LEVELS.len() - 1)].apply(difficulty);
+            },
+            GameMode::Master { version } => {
+                score.level = master::level_after_lines(score.level, lines);
+                version.apply(score.level, difficulty);
+            },
+            _ => {},
+        }
+    }
+
+    // Lines left to clear, if mode has such goal
+    pub fn lines_left(&self, score: &Score) -> Option<u32> {
+        match self {
+            GameMode::Sprint { lines } => Some(lines.saturating_sub(score.lines)),
+            GameMode::Marathon { lines_per_level, levels } => Some((lines_per_level * levels).saturating_sub(score.lines)),
+            _ => None,
+        }
+    }
+
+    // Garbage lines left to dig, if mode has such goal
+    pub fn garbage_left(&self, stats: &Stats) -> Option<u32> {
+        match self {
+            GameMode::Dig { lines, .. } => Some(lines.saturating_sub(stats.garbage_cleared)),
+            _ => None,
+        }
+    }
+
+    // How many garbage rows mode wants to add, when board has rows_on_board of them
+    // and rows_added were added since start
+    pub fn garbage_top_up(&self, rows_on_board: u32, rows_added: u32) -> u32 {
+        match self {
+            GameMode::Dig { lines, visible } => visible.saturating_sub(rows_on_board).min(lines.saturating_sub(rows_added)),
+            _ => 0,
+        }
+    }
+
+    // Survival: every next interval between garbage rises is 10% shorter
+    pub fn garbage_rises(&self, frames_played: u64) -> bool {
+        match self {
+            GameMode::Survival { seconds } => {
+                if frames_played == 0 {
+                    return false;
+                }
+                let mut interval = (*seconds as u64 * FPS).max(SURVIVAL_MIN_INTERVAL);
+                let mut rises_at = interval;
+                while rises_at < frames_played {
+                    interval = (interval * 9 / 10).max(SURVIVAL_MIN_INTERVAL);
+                    rises_at += interval;
+                }
+                rises_at == frames_played
+            },
+            _ => false,
+        }
+    }
+
+    // Frames left before the game ends, if mode has time limit
+    pub fn frames_left(&self, frames_played: u64) -> Option<u64> {
+        match self {
+            GameMode::Ultra { seconds } => Some((*seconds as u64 * FPS).saturating_sub(frames_played)),
+            _ => None,
+        }
+    }
+
+    // Level where current section ends, if mode has sections
+    pub fn level_goal(&self, score: &Score) -> Option<u32> {
+        match self {
+            GameMode::Master { .. } => Some(master::section_goal(score.level)),
+            _ => None,
+        }
+    }
+
+    // Modes, where topping out is the normal way to end the game
+    pub fn results_on_top_out(&self) -> bool {
+        matches!(self, GameMode::Master { .. } | GameMode::Ultra { .. } | GameMode::Survival { .. })
+    }
+
+    // Timed modes can't wait for the first input, otherwise clock can be held back forever
+    pub fn clock_starts_on_spawn(&self) -> bool {
+        matches!(self, GameMode::Ultra { .. } | GameMode::Survival { .. })
+    }
+
+    pub fn grade(&self, score: &Score, stats: &Stats, frame: u64) -> Option<&'static str> {
+        match self {
+            GameMode::Master { .. } => Some(master::grade(score, stats, frame)),
+            _ => None,
+        }
+    }
+
+    pub fn is_finished(&self, score: &Score, stats: &Stats, frame: u64) -> bool {
+        match self {
+            GameMode::Master { .. } => score.level >= MAX_LEVEL,
+            GameMode::Dig { .. } => self.garbage_left(stats) == Some(0),
+            _ => self.lines_left(score) == Some(0) || self.frames_left(stats.frames(frame)) == Some(0),
+        }
+    }
+}
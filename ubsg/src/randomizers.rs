@@ -0,0 +1,222 @@
+use rand::{seq::SliceRandom, Rng, SeedableRng, random};
+use rand::rngs::StdRng;
+
+use crate::{rotation_systems::PiecesData, game::Piece};
+
+pub trait Randomizer{
+    // Same seed always gives the same sequence of pieces
+    fn create(seed: u64) -> Self where Self: Sized;
+    fn seed(&self) -> u64;
+    fn name(&self) -> &'static str;
+    fn populate_next(&mut self, pieces_data: &PiecesData, board_width: isize, board_height: isize) -> Vec<Piece>;
+}
+
+pub const RANDOMIZERS: [&str; 4] = ["Bag", "BagX2", "RandomWithoutDirectRepetition", "TGM"];
+
+pub fn random_seed() -> u64 {
+    random::<u64>()
+}
+
+pub fn create_randomizer(name: &str, seed: u64) -> Option<Box<dyn Randomizer + Sync + Send>> {
+    match name {
+        "Bag" => Some(Box::new(Bag::create(seed))),
+        "BagX2" => Some(Box::new(BagX2::create(seed))),
+        "RandomWithoutDirectRepetition" => Some(Box::new(RandomWithoutDirectRepetition::create(seed))),
+        "TGM" => Some(Box::new(TGM::create(seed))),
+        _ => None
+    }
+}
+
+pub struct Bag {
+    seed: u64,
+    rng: StdRng
+}
+
+pub struct BagX2 {
+    seed: u64,
+    rng: StdRng
+}
+
+pub struct RandomWithoutDirectRepetition {
+    memory: usize,
+    seed: u64,
+    rng: StdRng
+}
+
+pub struct TGM {
+    memory: Vec<usize>,
+    initial_seed: u64,
+    seed: u32
+}
+
+impl Randomizer for Bag {
+    fn populate_next(&mut self, pieces_data: &PiecesData, board_width: isize, board_height: isize) -> Vec<Piece> {
+        let mut bag = vec![];
+        for id in 0..pieces_data.pieces.len() {
+            bag.insert(id, Piece::create(pieces_data, id, board_width, board_height));
+        }
+        bag.shuffle(&mut self.rng);
+        bag
+    }
+
+    fn create(seed: u64) -> Self where Self: Sized {
+        Bag { seed, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn name(&self) -> &'static str {
+        "Bag"
+    }
+}
+
+impl Randomizer for BagX2 {
+    fn populate_next(&mut self, pieces_data: &PiecesData, board_width: isize, board_height: isize) -> Vec<Piece> {
+        let mut bag = vec![];
+        for id in 0..pieces_data.pieces.len() {
+            bag.insert(id, Piece::create(pieces_data, id, board_width, board_height));
+            bag.insert(id, Piece::create(pieces_data, id, board_width, board_height));
+        }
+        bag.shuffle(&mut self.rng);
+        bag
+    }
+
+    fn create(seed: u64) -> Self where Self: Sized {
+        BagX2 { seed, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn name(&self) -> &'static str {
+        "BagX2"
+    }
+}
+
+impl Randomizer for RandomWithoutDirectRepetition {
+    fn populate_next(&mut self, pieces_data: &PiecesData, board_width: isize, board_height: isize) -> Vec<Piece> {
+        let random_number = self.rng.gen_range(0..pieces_data.pieces.len());
+        if random_number != self.memory {
+            self.memory = random_number;
+            vec![Piece::create(pieces_data, random_number, board_width, board_height)]
+        }else{
+            let random_number = self.rng.gen_range(0..pieces_data.pieces.len());
+            self.memory = random_number;
+            vec![Piece::create(pieces_data, random_number, board_width, board_height)]
+        }
+        
+    }
+
+    fn create(seed: u64) -> Self where Self: Sized {
+        RandomWithoutDirectRepetition { memory: 65535, seed, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn name(&self) -> &'static str {
+        "RandomWithoutDirectRepetition"
+    }
+}
+
+impl TGM {
+    fn random(n: u32) -> u32{
+        n.wrapping_mul(0x41c64e6d).wrapping_add(12345)
+    }
+
+    fn read(&mut self) -> u32 {
+        self.seed = TGM::random(self.seed);
+        (self.seed >> 10) & 0x7fff
+    }
+}
+
+impl Randomizer for TGM {
+    fn populate_next(&mut self, pieces_data: &PiecesData, board_width: isize, board_height: isize) -> Vec<Piece> {
+        let mut b = 0;
+        for _ in 0..4{
+            b = self.read() % 7;
+            if !self.memory.contains(&(b as usize)){break;}
+            b = self.read() % 7;
+        }
+        self.memory.pop();
+        self.memory.insert(0, b as usize);
+        vec![Piece::create(pieces_data, b as usize, board_width, board_height)]
+    }
+
+    fn create(seed: u64) -> Self where Self: Sized {
+        let mut amazon_prime = TGM {
+            memory: vec![],
+            initial_seed: seed,
+            seed: (seed ^ (seed >> 32)) as u32 // TGM LCG works with 32-bit state, high half is folded in
+        };
+        let mut b = 0;
+        while b == 0 || b == 6 || b == 4 {
+            b = amazon_prime.read() as usize % 7;
+        }
+        amazon_prime.memory = vec![b, 0, 0, 0];
+        amazon_prime
+    }
+
+    fn seed(&self) -> u64 {
+        self.initial_seed
+    }
+
+    fn name(&self) -> &'static str {
+        "TGM"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rotation_systems::ROTATION_SYSTEMS;
+
+    fn sequence(name: &str, seed: u64) -> Vec<usize> {
+        let pieces_data = &ROTATION_SYSTEMS["SRS"];
+        let mut randomizer = create_randomizer(name, seed).unwrap();
+        let mut ids = vec![];
+        while ids.len() < 70 {
+            ids.extend(randomizer.populate_next(pieces_data, 10, 20).iter().map(|piece| piece.id));
+        }
+        ids
+    }
+
+    #[test]
+    fn same_seed_gives_same_pieces() {
+        for name in RANDOMIZERS {
+            assert_eq!(sequence(name, 42), sequence(name, 42), "{}", name);
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_pieces() {
+        for name in RANDOMIZERS {
+            assert_ne!(sequence(name, 1), sequence(name, 2), "{}", name);
+        }
+    }
+
+    #[test]
+    fn randomizer_remembers_its_seed() {
+        for name in RANDOMIZERS {
+            assert_eq!(create_randomizer(name, 1234).unwrap().seed(), 1234, "{}", name);
+        }
+    }
+
+    #[test]
+    fn tgm_uses_whole_seed() {
+        assert_ne!(sequence("TGM", 5), sequence("TGM", 5 + (1 << 32)));
+    }
+
+    #[test]
+    fn bag_deals_every_piece_once() {
+        for bag in sequence("Bag", 7).chunks(7) {
+            let mut bag = bag.to_vec();
+            bag.sort();
+            assert_eq!(bag, vec![0, 1, 2, 3, 4, 5, 6]);
+        }
+    }
+}
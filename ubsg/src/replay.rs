@@ -0,0 +1,201 @@
+use std::{fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{game::{Difficulty, Engine, Handling, Inputs}, randomizers::create_randomizer, rotation_systems::ROTATION_SYSTEMS, scoring::ScoringTable, modes::GameMode, attack::AttackTable, ron_file::{self, RonFileError}};
+
+// Bump it when the format or the game rules change in a way that breaks old replays
+pub const REPLAY_VERSION: u32 = 2;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub rotation_system: String,
+    pub randomizer: String,
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub das: f32, // ms
+    pub arr: f32, // ms
+    pub sdf: f32,
+    #[serde(default)]
+    pub scoring: ScoringTable,
+    #[serde(default)]
+    pub mode: GameMode,
+    #[serde(default)]
+    pub attack_table: AttackTable,
+    pub frames: u64, // length of the replay
+    // Inputs are stored only on frames where they changed (frame, inputs from that frame)
+    pub inputs: Vec<(u64, Inputs)>
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    File(RonFileError),
+    Version(u32),
+    UnknownRotationSystem(String),
+    UnknownRandomizer(String)
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::File(error) => write!(f, "replay {}", error),
+            ReplayError::Version(version) => write!(f, "replay version {} is not supported, expected {}", version, REPLAY_VERSION),
+            ReplayError::UnknownRotationSystem(name) => write!(f, "unknown rotation system {}", name),
+            ReplayError::UnknownRandomizer(name) => write!(f, "unknown randomizer {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<RonFileError> for ReplayError {
+    fn from(error: RonFileError) -> Self {
+        ReplayError::File(error)
+    }
+}
+
+impl Replay {
+    // Takes settings from engine, that was just initialized
+    pub fn create(engine: &Engine) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            rotation_system: engine.rotation_system_name.clone(),
+            randomizer: String::from(engine.randomizer.name()),
+            seed: engine.randomizer.seed(),
+            difficulty: engine.difficulty.clone(),
+            das: engine.handling.das,
+            arr: engine.handling.arr,
+            sdf: engine.handling.sdf,
+            scoring: engine.score.table,
+            mode: engine.mode,
+            attack_table: engine.attack_table,
+            frames: 0,
+            inputs: vec![]
+        }
+    }
+
+    pub fn record(&mut self, frame: u64, inputs: &Inputs) {
+        let changed = match self.inputs.last() {
+            Some((_, last)) => last != inputs,
+            None => *inputs != Inputs::default(),
+        };
+        if changed {
+            self.inputs.push((frame, *inputs));
+        }
+        self.frames = frame + 1;
+    }
+
+    // Engine in the same state as the one replay was recorded from
+    pub fn create_engine(&self) -> Result<Engine, ReplayError> {
+        if !ROTATION_SYSTEMS.contains_key(&self.rotation_system) {
+            return Err(ReplayError::UnknownRotationSystem(self.rotation_system.clone()));
+        }
+        let randomizer = match create_randomizer(&self.randomizer, self.seed) {
+            Some(randomizer) => randomizer,
+            None => return Err(ReplayError::UnknownRandomizer(self.randomizer.clone())),
+        };
+        let mut engine = Engine {
+            difficulty: self.difficulty.clone(),
+            attack_table: self.attack_table,
+            handling: Handling::create(self.das, self.arr, self.sdf),
+            ..Default::default()
+        };
+        engine.init(&self.rotation_system, randomizer, self.scoring, self.mode);
+        Ok(engine)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        ron_file::save(self, path).map_err(ReplayError::File)
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, ReplayError> {
+        let replay: Replay = ron_file::load(path)?;
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(replay.version));
+        }
+        Ok(replay)
+    }
+}
+
+// Feeds recorded inputs back frame by frame
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    position: usize,
+    current: Inputs
+}
+
+impl ReplayPlayer {
+    pub fn create(replay: Replay) -> ReplayPlayer {
+        ReplayPlayer { replay, position: 0, current: Inputs::default() }
+    }
+
+    pub fn inputs(&mut self, frame: u64) -> Inputs {
+        while self.position < self.replay.inputs.len() && self.replay.inputs[self.position].0 <= frame {
+            self.current = self.replay.inputs[self.position].1;
+            self.position += 1;
+        }
+        self.current
+    }
+
+    pub fn is_finished(&self, frame: u64) -> bool {
+        frame >= self.replay.frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::GameloopStates, randomizers::{Randomizer, TGM}, modes::GameMode};
+
+    fn occupied(engine: &Engine) -> Vec<bool> {
+        engine.board.board.iter().flatten().map(|cell| cell.is_some()).collect()
+    }
+
+    #[test]
+    fn replay_reproduces_the_game() {
+        let mut engine = Engine::default();
+        engine.init("SRS", Box::new(TGM::create(42)), ScoringTable::Guideline, GameMode::Endless);
+        let mut replay = Replay::create(&engine);
+        // pseudo random presses, same every run
+        let mut state: u64 = 7;
+        while engine.state != GameloopStates::GameOver && engine.frame < 5000 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            let roll = (state >> 33) as u32;
+            let inputs = Inputs {
+                left: roll.is_multiple_of(5),
+                right: roll.is_multiple_of(7),
+                soft_drop: roll.is_multiple_of(11),
+                hard_drop: roll.is_multiple_of(23),
+                rotate_cw: roll.is_multiple_of(3),
+                ..Default::default()
+            };
+            replay.record(engine.frame, &inputs);
+            engine.step(&inputs);
+        }
+        assert!(occupied(&engine).contains(&true));
+
+        let path = std::env::temp_dir().join(format!("ubsg-replay-test-{}.ron", std::process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut replayed = loaded.create_engine().unwrap();
+        let mut player = ReplayPlayer::create(loaded);
+        while !player.is_finished(replayed.frame) {
+            let inputs = player.inputs(replayed.frame);
+            replayed.step(&inputs);
+        }
+        assert_eq!(occupied(&replayed), occupied(&engine));
+        assert_eq!(replayed.score.score, engine.score.score);
+        assert_eq!(replayed.frame, engine.frame);
+        assert!(replayed.state == engine.state);
+    }
+
+    #[test]
+    fn unknown_rules_are_rejected() {
+        let mut replay = Replay::create(&Engine::default());
+        replay.rotation_system = String::from("NoSuchRS");
+        assert!(matches!(replay.create_engine(), Err(ReplayError::UnknownRotationSystem(_))));
+    }
+}
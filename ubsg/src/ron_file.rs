@@ -0,0 +1,55 @@
+use std::{fmt, fs, io, path::Path};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+// Settings, controls and replays are all kept as pretty RON files
+#[derive(Debug)]
+pub enum RonFileError {
+    Io(io::Error),
+    Format(String)
+}
+
+impl fmt::Display for RonFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RonFileError::Io(error) => write!(f, "can't access file: {}", error),
+            RonFileError::Format(error) => write!(f, "file is broken: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for RonFileError {}
+
+impl From<io::Error> for RonFileError {
+    fn from(error: io::Error) -> Self {
+        RonFileError::Io(error)
+    }
+}
+
+impl RonFileError {
+    // Missing file usually means that defaults should be used
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, RonFileError::Io(error) if error.kind() == io::ErrorKind::NotFound)
+    }
+}
+
+// Creates missing directories of the path
+pub fn save<T: Serialize>(value: &T, path: &Path) -> Result<(), RonFileError> {
+    let text = match ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()) {
+        Ok(text) => text,
+        Err(error) => return Err(RonFileError::Format(error.to_string())),
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
+
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, RonFileError> {
+    let text = fs::read_to_string(path)?;
+    match ron::from_str(&text) {
+        Ok(value) => Ok(value),
+        Err(error) => Err(RonFileError::Format(error.to_string())),
+    }
+}
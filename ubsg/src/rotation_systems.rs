@@ -1,6 +1,16 @@
-use bevy::{utils::HashMap, prelude::Color};
+use std::collections::HashMap;
 use lazy_static::lazy_static;
 
+use crate::game::Color;
+
+// Every rotation system lists pieces in the same order: Z, J, I, T, O, L, S
+pub const T_PIECE: usize = 3;
+
+// Directions of rotation in kick tables
+pub const KICKS_CW: usize = 0;
+pub const KICKS_CCW: usize = 1;
+pub const KICKS_180: usize = 2;
+
 #[derive(Clone)]
 pub enum LockDelayMode{
     Disabled,
@@ -10,12 +20,17 @@ pub enum LockDelayMode{
     ResetOnMovement
 }
 
+// X and Y shifts tried in order until one fits
+pub type KickTests = Vec<(i8, i8)>;
+
 #[derive(Clone)]
 pub struct PiecesData {
     // X and Y from bottom left point (pieces[piece][rotation] = Vec of coords for Minos)
     pub pieces: Vec<Vec<Vec<(u8, u8)>>>,
     // X and Y shifts for pieces (kicks[piece][rotation before spin][direction of rotation] = Vec of points for tests)
-    pub kicks: Vec<Vec<Vec<Vec<(i8, i8)>>>>,
+    pub kicks: Vec<Vec<Vec<KickTests>>>,
+    // 180 spins are possible only with that, kick tables need KICKS_180 direction then
+    pub allow_180: bool,
     pub colours: Vec<Color>,
     // If spawn position is fucked, it fixes it
     pub spawn_offsets: Vec<(isize, isize)>,
@@ -69,127 +84,153 @@ lazy_static!{
                     vec![(0, 2), (0, 1), (1, 1), (1, 0)]
                 ]
             ],
+            // 180 kicks are from SRS+ (TETR.IO), same for every piece
             kicks: vec![
                 vec![ // Z
                     vec![
                         vec![( 0, 0),(-1, 0),(-1, 1),( 0,-2),(-1,-2)], // 0 -> 90
                         vec![( 0, 0),( 1, 0),( 1, 1),( 0,-2),( 1,-2)], // 0 -> 270
+                        vec![( 0, 0),( 0, 1),( 1, 1),(-1, 1),( 1, 0),(-1, 0)], // 0 -> 180
                         ],
                     vec![
                         vec![( 0, 0),( 1, 0),( 1,-1),( 0, 2),( 1, 2)], // 90 -> 180
                         vec![( 0, 0),( 1, 0),( 1,-1),( 0, 2),( 1, 2)], // 90 -> 0
+                        vec![( 0, 0),( 1, 0),( 1, 2),( 1, 1),( 0, 2),( 0, 1)], // 90 -> 270
                         ],
                     vec![
                         vec![( 0, 0),( 1, 0),( 1, 1),( 0,-2),( 1,-2)], // 180 -> 270
                         vec![( 0, 0),(-1, 0),(-1, 1),( 0,-2),(-1,-2)], // 180 -> 90
+                        vec![( 0, 0),( 0,-1),(-1,-1),( 1,-1),(-1, 0),( 1, 0)], // 180 -> 0
                         ], 
                     vec![
                         vec![( 0, 0),(-1, 0),(-1,-1),( 0, 2),(-1, 2)], // 270 -> 0
                         vec![( 0, 0),(-1, 0),(-1,-1),( 0, 2),(-1, 2)], // 270 -> 180
+                        vec![( 0, 0),(-1, 0),(-1, 2),(-1, 1),( 0, 2),( 0, 1)], // 270 -> 90
                         ] 
                 ],
                 vec![ // J
                     vec![
                         vec![( 0, 0),(-1, 0),(-1, 1),( 0,-2),(-1,-2)], // 0 -> 90
                         vec![( 0, 0),( 1, 0),( 1, 1),( 0,-2),( 1,-2)], // 0 -> 270
+                        vec![( 0, 0),( 0, 1),( 1, 1),(-1, 1),( 1, 0),(-1, 0)], // 0 -> 180
                         ],
                     vec![
                         vec![( 0, 0),( 1, 0),( 1,-1),( 0, 2),( 1, 2)], // 90 -> 180
                         vec![( 0, 0),( 1, 0),( 1,-1),( 0, 2),( 1, 2)], // 90 -> 0
+                        vec![( 0, 0),( 1, 0),( 1, 2),( 1, 1),( 0, 2),( 0, 1)], // 90 -> 270
                         ],
                     vec![
                         vec![( 0, 0),( 1, 0),( 1, 1),( 0,-2),( 1,-2)], // 180 -> 270
                         vec![( 0, 0),(-1, 0),(-1, 1),( 0,-2),(-1,-2)], // 180 -> 90
+                        vec![( 0, 0),( 0,-1),(-1,-1),( 1,-1),(-1, 0),( 1, 0)], // 180 -> 0
                         ], 
                     vec![
                         vec![( 0, 0),(-1, 0),(-1,-1),( 0, 2),(-1, 2)], // 270 -> 0
                         vec![( 0, 0),(-1, 0),(-1,-1),( 0, 2),(-1, 2)], // 270 -> 180
+                        vec![( 0, 0),(-1, 0),(-1, 2),(-1, 1),( 0, 2),( 0, 1)], // 270 -> 90
                         ] 
                 ],
                 vec![ // I
                     vec![
                         vec![( 0, 0),(-2, 0),( 1, 0),(-2,-1),( 1, 2)], // 0 -> 90
                         vec![( 0, 0),(-1, 0),( 2, 0),(-1, 2),( 2,-1)], // 0 -> 270
+                        vec![( 0, 0),( 0, 1),( 1, 1),(-1, 1),( 1, 0),(-1, 0)], // 0 -> 180
                         ],
                     vec![
                         vec![( 0, 0),(-1, 0),( 2, 0),(-1, 2),( 2,-1)], // 90 -> 180
                         vec![( 0, 0),( 2, 0),(-1, 0),( 2, 1),(-1,-2)], // 90 -> 0
+                        vec![( 0, 0),( 1, 0),( 1, 2),( 1, 1),( 0, 2),( 0, 1)], // 90 -> 270
                         ],
                     vec![
                         vec![( 0, 0),( 2, 0),(-1, 0),( 2, 1),(-1,-2)], // 180 -> 270
                         vec![( 0, 0),( 1, 0),(-2, 0),( 1,-2),(-2, 1)], // 180 -> 90
+                        vec![( 0, 0),( 0,-1),(-1,-1),( 1,-1),(-1, 0),( 1, 0)], // 180 -> 0
                         ],
                     vec![
                         vec![( 0, 0),( 1, 0),(-2, 0),( 1,-2),(-2, 1)], // 270 -> 0
                         vec![( 0, 0),(-2, 0),( 1, 0),(-2,-1),( 1, 2)], // 270 -> 180
+                        vec![( 0, 0),(-1, 0),(-1, 2),(-1, 1),( 0, 2),( 0, 1)], // 270 -> 90
                         ]
                 ],
                 vec![ // T
                     vec![
                         vec![( 0, 0),(-1, 0),(-1, 1),( 0,-2),(-1,-2)], // 0 -> 90
                         vec![( 0, 0),( 1, 0),( 1, 1),( 0,-2),( 1,-2)], // 0 -> 270
+                        vec![( 0, 0),( 0, 1),( 1, 1),(-1, 1),( 1, 0),(-1, 0)], // 0 -> 180
                         ],
                     vec![
                         vec![( 0, 0),( 1, 0),( 1,-1),( 0, 2),( 1, 2)], // 90 -> 180
                         vec![( 0, 0),( 1, 0),( 1,-1),( 0, 2),( 1, 2)], // 90 -> 0
+                        vec![( 0, 0),( 1, 0),( 1, 2),( 1, 1),( 0, 2),( 0, 1)], // 90 -> 270
                         ],
                     vec![
                         vec![( 0, 0),( 1, 0),( 1, 1),( 0,-2),( 1,-2)], // 180 -> 270
                         vec![( 0, 0),(-1, 0),(-1, 1),( 0,-2),(-1,-2)], // 180 -> 90
+                        vec![( 0, 0),( 0,-1),(-1,-1),( 1,-1),(-1, 0),( 1, 0)], // 180 -> 0
                         ], 
                     vec![
                         vec![( 0, 0),(-1, 0),(-1,-1),( 0, 2),(-1, 2)], // 270 -> 0
                         vec![( 0, 0),(-1, 0),(-1,-1),( 0, 2),(-1, 2)], // 270 -> 180
+                        vec![( 0, 0),(-1, 0),(-1, 2),(-1, 1),( 0, 2),( 0, 1)], // 270 -> 90
                         ] 
                 ],
                 vec![ // O
                     vec![
                         vec![( 0, 0)], // 0 -> 90
                         vec![( 0, 0)], // 0 -> 270
+                        vec![( 0, 0)], // 0 -> 0
                         ],
                 ],
                 vec![ // L
                     vec![
                         vec![( 0, 0),(-1, 0),(-1, 1),( 0,-2),(-1,-2)], // 0 -> 90
                         vec![( 0, 0),( 1, 0),( 1, 1),( 0,-2),( 1,-2)], // 0 -> 270
+                        vec![( 0, 0),( 0, 1),( 1, 1),(-1, 1),( 1, 0),(-1, 0)], // 0 -> 180
                         ],
                     vec![
                         vec![( 0, 0),( 1, 0),( 1,-1),( 0, 2),( 1, 2)], // 90 -> 180
                         vec![( 0, 0),( 1, 0),( 1,-1),( 0, 2),( 1, 2)], // 90 -> 0
+                        vec![( 0, 0),( 1, 0),( 1, 2),( 1, 1),( 0, 2),( 0, 1)], // 90 -> 270
                         ],
                     vec![
                         vec![( 0, 0),( 1, 0),( 1, 1),( 0,-2),( 1,-2)], // 180 -> 270
                         vec![( 0, 0),(-1, 0),(-1, 1),( 0,-2),(-1,-2)], // 180 -> 90
+                        vec![( 0, 0),( 0,-1),(-1,-1),( 1,-1),(-1, 0),( 1, 0)], // 180 -> 0
                         ], 
                     vec![
                         vec![( 0, 0),(-1, 0),(-1,-1),( 0, 2),(-1, 2)], // 270 -> 0
                         vec![( 0, 0),(-1, 0),(-1,-1),( 0, 2),(-1, 2)], // 270 -> 180
+                        vec![( 0, 0),(-1, 0),(-1, 2),(-1, 1),( 0, 2),( 0, 1)], // 270 -> 90
                         ] 
                 ],
                 vec![ // S
                     vec![
                         vec![( 0, 0),(-1, 0),(-1, 1),( 0,-2),(-1,-2)], // 0 -> 90
                         vec![( 0, 0),( 1, 0),( 1, 1),( 0,-2),( 1,-2)], // 0 -> 270
+                        vec![( 0, 0),( 0, 1),( 1, 1),(-1, 1),( 1, 0),(-1, 0)], // 0 -> 180
                         ],
                     vec![
                         vec![( 0, 0),( 1, 0),( 1,-1),( 0, 2),( 1, 2)], // 90 -> 180
                         vec![( 0, 0),( 1, 0),( 1,-1),( 0, 2),( 1, 2)], // 90 -> 0
+                        vec![( 0, 0),( 1, 0),( 1, 2),( 1, 1),( 0, 2),( 0, 1)], // 90 -> 270
                         ],
                     vec![
                         vec![( 0, 0),( 1, 0),( 1, 1),( 0,-2),( 1,-2)], // 180 -> 270
                         vec![( 0, 0),(-1, 0),(-1, 1),( 0,-2),(-1,-2)], // 180 -> 90
+                        vec![( 0, 0),( 0,-1),(-1,-1),( 1,-1),(-1, 0),( 1, 0)], // 180 -> 0
                         ], 
                     vec![
                         vec![( 0, 0),(-1, 0),(-1,-1),( 0, 2),(-1, 2)], // 270 -> 0
                         vec![( 0, 0),(-1, 0),(-1,-1),( 0, 2),(-1, 2)], // 270 -> 180
+                        vec![( 0, 0),(-1, 0),(-1, 2),(-1, 1),( 0, 2),( 0, 1)], // 270 -> 90
                         ] 
                 ],
             ],
             colours: vec![
                 Color::RED,    // Z
-                Color::Rgba { red: 0.0, green: 0.3, blue: 1.0, alpha: 1.0 },  // J
+                Color { red: 0.0, green: 0.3, blue: 1.0, alpha: 1.0 },  // J
                 Color::CYAN,   // I
-                Color::Rgba { red: 1.0, green: 0.0, blue: 1.0, alpha: 1.0 },  // T
+                Color { red: 1.0, green: 0.0, blue: 1.0, alpha: 1.0 },  // T
                 Color::YELLOW, // O
                 Color::ORANGE, // L
                 Color::GREEN   // Z
@@ -205,6 +246,7 @@ lazy_static!{
             ],
             lock_delay_mode: LockDelayMode::ResetOnMovementLimited,
             height_offset: 0,
+            allow_180: true
         });
         rs.insert(String::from("NRS"), PiecesData {
             pieces: vec![
@@ -279,9 +321,9 @@ lazy_static!{
             ],
             colours: vec![
                 Color::RED,    // Z
-                Color::Rgba { red: 0.0, green: 0.3, blue: 1.0, alpha: 1.0 },  // J
+                Color { red: 0.0, green: 0.3, blue: 1.0, alpha: 1.0 },  // J
                 Color::CYAN,   // I
-                Color::Rgba { red: 1.0, green: 0.0, blue: 1.0, alpha: 1.0 },  // T
+                Color { red: 1.0, green: 0.0, blue: 1.0, alpha: 1.0 },  // T
                 Color::YELLOW, // O
                 Color::ORANGE, // L
                 Color::GREEN   // Z
@@ -296,7 +338,8 @@ lazy_static!{
                 (0,  0)  // S
             ],
             height_offset: -2,
-            lock_delay_mode: LockDelayMode::Gravity 
+            lock_delay_mode: LockDelayMode::Gravity,
+            allow_180: false
         });
         rs.insert(String::from("ARS"), PiecesData {
             pieces: vec![
@@ -436,12 +479,12 @@ lazy_static!{
             ],
             colours: vec![
                 Color::GREEN,    // Z
-                Color::Rgba { red: 0.0, green: 0.3, blue: 1.0, alpha: 1.0 },  // J
+                Color { red: 0.0, green: 0.3, blue: 1.0, alpha: 1.0 },  // J
                 Color::RED,   // I
                 Color::CYAN,  // T
                 Color::YELLOW, // O
                 Color::ORANGE, // L
-                Color::Rgba { red: 1.0, green: 0.0, blue: 1.0, alpha: 1.0 }  // s
+                Color { red: 1.0, green: 0.0, blue: 1.0, alpha: 1.0 }  // s
             ],
             spawn_offsets: vec![
                 (0,  0), // Z
@@ -453,7 +496,8 @@ lazy_static!{
                 (0,  0)  // S
             ],
             lock_delay_mode: LockDelayMode::ResetOnYChange,
-            height_offset: -2 
+            height_offset: -2,
+            allow_180: false
         });
         rs
     };
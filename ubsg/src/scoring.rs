@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+// Line clears are multiplied by level. Level changes only in modes with level goals (Marathon, Master),
+// in other modes it stays at starting level, so the multiplier is always 1
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ScoringTable {
+    #[default]
+    Guideline, // 100/300/500/800 * level, 1 per soft dropped cell, 2 per hard dropped cell
+    Classic,   // NES: 40/100/300/1200 * (level + 1), 1 per soft dropped cell, no hard drop
+    Master     // TGM: ceil((level + lines) / 4) * lines, 1 per soft dropped cell, no hard drop. Combo and bravo bonuses aren't counted
+}
+
+// Tables that can be picked in the menu, Master is used only by its mode
+pub const SCORING_TABLES: [ScoringTable; 2] = [ScoringTable::Guideline, ScoringTable::Classic];
+
+impl ScoringTable {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScoringTable::Guideline => "Guideline",
+            ScoringTable::Classic => "Classic",
+            ScoringTable::Master => "Master",
+        }
+    }
+
+    pub fn starting_level(&self) -> u32 {
+        match self {
+            ScoringTable::Guideline => 1,
+            ScoringTable::Classic => 0,
+            ScoringTable::Master => 0,
+        }
+    }
+
+    fn line_clear_points(&self, lines: usize, level: u32) -> u64 {
+        let (base, multiplier) = match self {
+            ScoringTable::Guideline => (
+                match lines {
+                    0 => 0,
+                    1 => 100,
+                    2 => 300,
+                    3 => 500,
+                    _ => 800,
+                },
+                level
+            ),
+            ScoringTable::Classic => (
+                match lines {
+                    0 => 0,
+                    1 => 40,
+                    2 => 100,
+                    3 => 300,
+                    _ => 1200,
+                },
+                level + 1
+            ),
+            ScoringTable::Master => ((level + lines as u32).div_ceil(4) as u64, lines as u32),
+        };
+        base * multiplier as u64
+    }
+
+    fn soft_drop_points(&self, cells: u32) -> u64 {
+        cells as u64
+    }
+
+    fn hard_drop_points(&self, cells: u32) -> u64 {
+        match self {
+            ScoringTable::Guideline => cells as u64 * 2,
+            ScoringTable::Classic => 0,
+            ScoringTable::Master => 0,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Score {
+    pub table: ScoringTable,
+    pub score: u64,
+    pub level: u32,
+    pub lines: u32,
+    pub drop_points: u64, // from soft and hard drops
+    // clears[n-1] = how many times n lines were cleared at once; quads and bigger go to the last one
+    pub clears: [u32; 4],
+    pub spin_clears: u32 // line clears made with T-spins, they are counted in clears too
+}
+
+impl Score {
+    pub fn create(table: ScoringTable) -> Score {
+        Score { table, level: table.starting_level(), ..Default::default() }
+    }
+
+    pub fn soft_drop(&mut self, cells: u32) {
+        let points = self.table.soft_drop_points(cells);
+        self.drop_points += points;
+        self.score += points;
+    }
+
+    pub fn hard_drop(&mut self, cells: u32) {
+        let points = self.table.hard_drop_points(cells);
+        self.drop_points += points;
+        self.score += points;
+    }
+
+    pub fn line_clear(&mut self, lines: usize) {
+        if lines == 0 {
+            return;
+        }
+        self.score += self.table.line_clear_points(lines, self.level);
+        self.lines += lines as u32;
+        self.clears[lines.min(4) - 1] += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cleared(table: ScoringTable, level: u32, lines: usize) -> u64 {
+        let mut score = Score::create(table);
+        score.level = level;
+        score.line_clear(lines);
+        score.score
+    }
+
+    #[test]
+    fn guideline_line_clears() {
+        assert_eq!(cleared(ScoringTable::Guideline, 1, 1), 100);
+        assert_eq!(cleared(ScoringTable::Guideline, 1, 2), 300);
+        assert_eq!(cleared(ScoringTable::Guideline, 1, 3), 500);
+        assert_eq!(cleared(ScoringTable::Guideline, 1, 4), 800);
+        assert_eq!(cleared(ScoringTable::Guideline, 5, 4), 4000);
+    }
+
+    #[test]
+    fn classic_line_clears() {
+        assert_eq!(cleared(ScoringTable::Classic, 0, 1), 40);
+        assert_eq!(cleared(ScoringTable::Classic, 0, 2), 100);
+        assert_eq!(cleared(ScoringTable::Classic, 0, 3), 300);
+        assert_eq!(cleared(ScoringTable::Classic, 0, 4), 1200);
+        assert_eq!(cleared(ScoringTable::Classic, 9, 4), 12000);
+    }
+
+    #[test]
+    fn master_line_clears() {
+        assert_eq!(cleared(ScoringTable::Master, 0, 1), 1);
+        assert_eq!(cleared(ScoringTable::Master, 0, 4), 4);
+        assert_eq!(cleared(ScoringTable::Master, 100, 4), 104);
+        assert_eq!(cleared(ScoringTable::Master, 997, 2), 500);
+    }
+
+    #[test]
+    fn drops() {
+        let mut guideline = Score::create(ScoringTable::Guideline);
+        guideline.soft_drop(3);
+        guideline.hard_drop(10);
+        assert_eq!((guideline.score, guideline.drop_points), (23, 23));
+
+        let mut classic = Score::create(ScoringTable::Classic);
+        classic.soft_drop(3);
+        classic.hard_drop(10);
+        assert_eq!((classic.score, classic.drop_points), (3, 3));
+
+        let mut master = Score::create(ScoringTable::Master);
+        master.soft_drop(3);
+        master.hard_drop(10);
+        assert_eq!((master.score, master.drop_points), (3, 3));
+    }
+
+    #[test]
+    fn clears_are_counted() {
+        let mut score = Score::create(ScoringTable::Guideline);
+        score.line_clear(0);
+        score.line_clear(1);
+        score.line_clear(4);
+        score.line_clear(4);
+        assert_eq!(score.lines, 9);
+        assert_eq!(score.clears, [1, 0, 0, 2]);
+    }
+}
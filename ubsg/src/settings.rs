@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{game::{Engine, Handling}, ron_file::{self, RonFileError}, randomizers::RANDOMIZERS, rotation_systems::ROTATION_SYSTEMS};
+
+// DAS, ARR and SDF of one player
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HandlingProfile {
+    pub das: f32, // ms
+    pub arr: f32, // ms
+    pub sdf: f32
+}
+
+impl Default for HandlingProfile {
+    fn default() -> HandlingProfile {
+        HandlingProfile { das: 200.0, arr: 33.0, sdf: 20.0 }
+    }
+}
+
+// Player's preferences, that are kept between launches. Missing fields take default values
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserSettings {
+    pub das: f32, // ms
+    pub arr: f32, // ms
+    pub sdf: f32,
+    pub versus_handling: [HandlingProfile; 2], // for player one and two, solo game uses fields above
+    pub next_count: u8,
+    pub ghost: bool,
+    pub grid: bool,
+    pub rotation_system: String,
+    pub randomizer: String
+}
+
+impl Default for UserSettings {
+    fn default() -> UserSettings {
+        UserSettings {
+            das: 200.0,
+            arr: 33.0,
+            sdf: 20.0,
+            versus_handling: [HandlingProfile::default(); 2],
+            next_count: 3,
+            ghost: true,
+            grid: true,
+            rotation_system: String::from("ARS"),
+            randomizer: String::from("Bag")
+        }
+    }
+}
+
+impl UserSettings {
+    // None for solo game, index of the player in versus
+    pub fn handling(&self, player: Option<usize>) -> HandlingProfile {
+        match player {
+            Some(player) => self.versus_handling[player.min(1)],
+            None => HandlingProfile { das: self.das, arr: self.arr, sdf: self.sdf },
+        }
+    }
+
+    // Handling and board look, call it before Engine::init
+    pub fn apply(&self, engine: &mut Engine, player: Option<usize>) {
+        let handling = self.handling(player);
+        engine.handling = Handling::create(handling.das, handling.arr, handling.sdf);
+        engine.board.show_next = self.next_count;
+        engine.board.show_shadow = self.ghost;
+        engine.board.show_grid = self.grid;
+        engine.difficulty.next_allowed = self.next_count;
+    }
+
+    // Stale or mistyped names in the file are replaced with defaults, Engine::init can't take them.
+    // Returns descriptions of what was replaced
+    pub fn reset_unknown_names(&mut self) -> Vec<String> {
+        let default = UserSettings::default();
+        let mut replaced = vec![];
+        if !ROTATION_SYSTEMS.contains_key(&self.rotation_system) {
+            replaced.push(format!("unknown rotation system {}, using {}", self.rotation_system, default.rotation_system));
+            self.rotation_system = default.rotation_system;
+        }
+        if !RANDOMIZERS.contains(&self.randomizer.as_str()) {
+            replaced.push(format!("unknown randomizer {}, using {}", self.randomizer, default.randomizer));
+            self.randomizer = default.randomizer;
+        }
+        replaced
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), RonFileError> {
+        ron_file::save(self, path)
+    }
+
+    pub fn load(path: &Path) -> Result<UserSettings, RonFileError> {
+        ron_file::load(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_names_are_reset() {
+        let mut settings: UserSettings = ron::from_str("(rotation_system: \"NoSuchRS\", randomizer: \"Bag\", das: 100.0)").unwrap();
+        assert_eq!(settings.reset_unknown_names().len(), 1);
+        assert_eq!(settings.rotation_system, UserSettings::default().rotation_system);
+        assert_eq!(settings.das, 100.0);
+        assert!(settings.reset_unknown_names().is_empty());
+    }
+}
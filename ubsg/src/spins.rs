@@ -0,0 +1,147 @@
+use crate::{game::{Board, Piece}, rotation_systems::{PiecesData, KICKS_180, T_PIECE}};
+
+// Kick test, that upgrades T-spin mini to full T-spin (SRS T kick with 1x2 offset). 180 kicks never upgrade
+const UPGRADING_KICK: usize = 4;
+
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub enum Placement {
+    #[default]
+    Regular,
+    TSpinMini,
+    TSpin
+}
+
+impl Placement {
+    pub fn is_spin(&self) -> bool {
+        *self != Placement::Regular
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Placement::Regular => "",
+            Placement::TSpinMini => "T-SPIN MINI",
+            Placement::TSpin => "T-SPIN",
+        }
+    }
+}
+
+fn is_occupied(board: &Board, x: isize, y: isize) -> bool {
+    // walls and floor count as occupied
+    match board.board.get(y as usize) {
+        Some(line) => match line.get(x as usize) {
+            Some(cell) => cell.is_some(),
+            None => true,
+        },
+        None => true,
+    }
+}
+
+// 3-corner rule: T piece, which was rotated last, with 3 of 4 corners around its center occupied.
+// It's a full T-spin if both corners it points to are occupied, otherwise it's mini
+pub fn classify_placement(board: &Board, pieces_data: &PiecesData, piece: &Piece) -> Placement {
+    if piece.id != T_PIECE {
+        return Placement::Regular;
+    }
+    let (direction, kick) = match piece.last_kick {
+        Some(last_kick) => last_kick,
+        None => return Placement::Regular,
+    };
+    let minos = &pieces_data.pieces[piece.id][piece.rotation];
+    let is_neighbour = |a: &(u8, u8), b: &(u8, u8)| (a.0 as isize - b.0 as isize).abs() + (a.1 as isize - b.1 as isize).abs() == 1;
+    // center is the only mino touching all three others
+    let center = match minos.iter().find(|a| minos.iter().filter(|b| is_neighbour(a, b)).count() == 3) {
+        Some(center) => (center.0 as isize, center.1 as isize),
+        None => return Placement::Regular,
+    };
+    // arms on the sides cancel each other, so the sum of arms is where T points
+    let mut pointing = (0, 0);
+    for mino in minos {
+        pointing.0 += mino.0 as isize - center.0;
+        pointing.1 += mino.1 as isize - center.1;
+    }
+    let center = (piece.position.0 + center.0, piece.position.1 + center.1);
+    let mut corners = 0;
+    let mut front_corners = 0;
+    for (dx, dy) in [(-1, -1), (-1, 1), (1, -1), (1, 1)] {
+        if is_occupied(board, center.0 + dx, center.1 + dy) {
+            corners += 1;
+            if dx == pointing.0 || dy == pointing.1 {
+                front_corners += 1;
+            }
+        }
+    }
+    if corners < 3 {
+        Placement::Regular
+    } else if front_corners == 2 || (kick == UPGRADING_KICK && direction != KICKS_180) {
+        Placement::TSpin
+    } else {
+        Placement::TSpinMini
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::{Color, Mino}, rotation_systems::{ROTATION_SYSTEMS, KICKS_CW, KICKS_180}};
+
+    const MINO: Option<Mino> = Some(Mino { color: Color::RED, locked_at: 0 });
+
+    // T-spin double slot: hole at x 4 on the bottom row, x 3-5 on the second one, overhang at x 3
+    fn slot() -> Board {
+        let mut board = Board::create(10, 20, 20, true, true, 3);
+        for x in 0..10 {
+            if x != 4 {
+                board.board[0][x] = MINO;
+            }
+            if !(3..=5).contains(&x) {
+                board.board[1][x] = MINO;
+            }
+        }
+        board.board[2][3] = MINO;
+        board
+    }
+
+    // T pointing down, its stem in the bottom hole
+    fn t_in_slot(last_kick: Option<(usize, usize)>) -> Piece {
+        let pieces_data = &ROTATION_SYSTEMS["SRS"];
+        Piece { rotation: 2, position: (3, 0), last_kick, ..Piece::create(pieces_data, T_PIECE, 10, 20) }
+    }
+
+    #[test]
+    fn three_corners_with_both_front_ones_is_t_spin() {
+        let placement = classify_placement(&slot(), &ROTATION_SYSTEMS["SRS"], &t_in_slot(Some((KICKS_CW, 0))));
+        assert_eq!(placement, Placement::TSpin);
+    }
+
+    #[test]
+    fn piece_moved_after_rotation_is_not_spin() {
+        let placement = classify_placement(&slot(), &ROTATION_SYSTEMS["SRS"], &t_in_slot(None));
+        assert_eq!(placement, Placement::Regular);
+    }
+
+    #[test]
+    fn two_corners_are_not_enough() {
+        let mut board = slot();
+        board.board[0][3] = None;
+        let placement = classify_placement(&board, &ROTATION_SYSTEMS["SRS"], &t_in_slot(Some((KICKS_CW, 0))));
+        assert_eq!(placement, Placement::Regular);
+    }
+
+    #[test]
+    fn one_front_corner_is_mini_unless_fifth_kick_was_used() {
+        let mut board = slot();
+        board.board[0][5] = None;
+        board.board[2][5] = MINO;
+        let pieces_data = &ROTATION_SYSTEMS["SRS"];
+        assert_eq!(classify_placement(&board, pieces_data, &t_in_slot(Some((KICKS_CW, 0)))), Placement::TSpinMini);
+        assert_eq!(classify_placement(&board, pieces_data, &t_in_slot(Some((KICKS_CW, UPGRADING_KICK)))), Placement::TSpin);
+        assert_eq!(classify_placement(&board, pieces_data, &t_in_slot(Some((KICKS_180, UPGRADING_KICK)))), Placement::TSpinMini);
+    }
+
+    #[test]
+    fn other_pieces_never_spin() {
+        let pieces_data = &ROTATION_SYSTEMS["SRS"];
+        let piece = Piece { id: 0, ..t_in_slot(Some((KICKS_CW, 0))) };
+        assert_eq!(classify_placement(&slot(), pieces_data, &piece), Placement::Regular);
+    }
+}
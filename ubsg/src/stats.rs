@@ -0,0 +1,69 @@
+use crate::{game::FRAME_DURATION, master::SECTION_LEVELS};
+
+pub const SPLIT_LINES: u32 = 10; // split is taken every time that many lines are cleared
+
+#[derive(Clone, Default)]
+pub struct Stats {
+    pub pieces: u32,
+    pub started_at: Option<u64>, // frame of the first input or the first spawn in timed modes
+    pub ended_at: Option<u64>, // frame when game was finished or topped out
+    pub splits: Vec<u64>, // frames since start
+    pub sections: Vec<(u64, u64)>, // frames since start and score, when every SECTION_LEVELS levels were passed
+    pub garbage_cleared: u32 // cleared lines, that had garbage in them
+}
+
+impl Stats {
+    pub fn start(&mut self, frame: u64) {
+        if self.started_at.is_none() {
+            self.started_at = Some(frame);
+        }
+    }
+
+    pub fn end(&mut self, frame: u64) {
+        if self.ended_at.is_none() {
+            self.ended_at = Some(frame);
+        }
+    }
+
+    // Frames since start, timer stops when game ends
+    pub fn frames(&self, frame: u64) -> u64 {
+        match self.started_at {
+            Some(started_at) => self.ended_at.unwrap_or(frame).saturating_sub(started_at),
+            None => 0,
+        }
+    }
+
+    pub fn time(&self, frame: u64) -> f32 {
+        self.frames(frame) as f32 * FRAME_DURATION
+    }
+
+    pub fn pieces_per_second(&self, frame: u64) -> f32 {
+        let time = self.time(frame);
+        if time > 0.0 {
+            self.pieces as f32 / time * 1000.0
+        } else {
+            0.0
+        }
+    }
+
+    pub fn lines_cleared(&mut self, frame: u64, lines_before: u32, lines_after: u32) {
+        for _ in lines_before / SPLIT_LINES..lines_after / SPLIT_LINES {
+            self.splits.push(self.frames(frame));
+        }
+    }
+
+    // Pieces used per cleared garbage line
+    pub fn pieces_per_garbage_line(&self) -> f32 {
+        if self.garbage_cleared > 0 {
+            self.pieces as f32 / self.garbage_cleared as f32
+        } else {
+            0.0
+        }
+    }
+
+    pub fn level_up(&mut self, frame: u64, level_before: u32, level_after: u32, score: u64) {
+        for _ in level_before / SECTION_LEVELS..level_after / SECTION_LEVELS {
+            self.sections.push((self.frames(frame), score));
+        }
+    }
+}